@@ -3,16 +3,30 @@
 use gtk4::prelude::{
     ApplicationExt, ApplicationExtManual, ButtonExt, FileChooserExt, FileExt, GtkWindowExt,
     TreeViewExt, CellRendererTextExt, EditableExt, NativeDialogExt, ObjectExt, StaticType, TreeModelExt,
-    TreeModelExtManual
+    TreeModelExtManual, ToggleButtonExt, RangeExt, WidgetExt
 };
 use gtk4::{
-    Application, ApplicationWindow, Builder, Button, CellRendererCombo, Editable,
-    FileChooserAction, FileChooserNative, ListStore, ResponseType, Stack, TreePath, TreeView,
+    glib, Application, ApplicationWindow, Builder, Button, CellRendererCombo, CellRendererText,
+    DrawingArea, DropDown, Editable, FileChooserAction, FileChooserNative, Label, LevelBar, ListStore,
+    Picture, ResponseType, Scale, SpinButton, Stack, ToggleButton, TreeIter, TreePath, TreeView,
 };
-use shared::client::ProjectorClient;
-use shared::{ProjectorCommand, Skip};
-use std::cell::RefCell;
+use shared::{ImageFormat, ProjectorCommand, ScaleMode, Track};
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
+use std::time::Duration;
+
+mod meters;
+mod player;
+mod playlist;
+mod preview;
+mod projectors;
+mod warp;
+
+// the preview is shown small, and a smaller image arrives sooner
+const SNAPSHOT_WIDTH: u32 = 640;
+// enough to see what is on screen without loading the projector or the network
+const PREVIEW_WIDTH: u32 = 480;
+const PREVIEW_FPS: u32 = 10;
 
 #[tokio::main]
 async fn main() {
@@ -21,9 +35,10 @@ async fn main() {
         .build();
 
     application.connect_activate(move |app| {
-        let projector_client = Rc::new(RefCell::new(None::<ProjectorClient>));
-
         let builder = Builder::from_string(include_str!("main_window.xml"));
+        let projectors = Rc::new(projectors::Projectors::new(
+            builder.object("projector_model").unwrap(),
+        ));
 
         let window: ApplicationWindow = builder
             .object("main_window")
@@ -36,10 +51,9 @@ async fn main() {
             let url_field = builder.object::<Editable>("url_entry").unwrap();
             let main_stack = main_stack.clone();
 
-            let mut projector_client = projector_client.clone();
+            let projectors = projectors.clone();
             connect_button.connect_clicked(move |_| {
-                *projector_client.borrow_mut() =
-                    Some(ProjectorClient::new(url_field.text().as_str()));
+                projectors.add(url_field.text().as_str());
                 main_stack.set_visible_child_name("projector_control_page");
             });
         }
@@ -49,30 +63,137 @@ async fn main() {
             if e.visible_child_name().unwrap().as_str() != "projector_control_page" {
                 return;
             } // maybe unnecessary
-            let projector_client = projector_client.clone();
+            let projectors = projectors.clone();
 
             let playlist_list: TreeView = builder.object("playlist_list").unwrap();
             let playlist_model: ListStore = builder.object("playlist_model").unwrap();
 
             playlist_list.set_model(Some(&playlist_model));
 
-            let mut rx = projector_client.borrow().as_ref().unwrap().subscribe();
+            // local preview of the selected item
+            let player = player::LocalPlayer::new(
+                builder.object("preview_video").unwrap(),
+                builder.object("preview_position_label").unwrap(),
+            );
+            playlist_list.selection().connect_changed(move |selection| {
+                if let Some((model, iter)) = selection.selected() {
+                    player.load(&model.get::<String>(&iter, playlist::COL_PATH as i32));
+                }
+            });
+
+            // output geometry, every edit is sent and the projector saves it
+            let warp_area: DrawingArea = builder.object("warp_area").unwrap();
+            let warp_columns_spin: SpinButton = builder.object("warp_columns_spin").unwrap();
+            let warp_rows_spin: SpinButton = builder.object("warp_rows_spin").unwrap();
+            let warp_editor = {
+                let projectors = projectors.clone();
+                Rc::new(warp::WarpEditor::new(warp_area, move |warp| {
+                    projectors.send(projectors.selected(), ProjectorCommand::SetWarp(warp.clone()));
+                }))
+            };
+            // set while the spin buttons follow the projector's warp, which would reset
+            // the mesh half way through
+            let syncing_warp = Rc::new(Cell::new(false));
+
+            let audio_device_options = ListStore::new(&[String::static_type()]);
+            playlist::set_audio_devices(&audio_device_options, &[]);
 
-            tokio::spawn(async move {
-                while let Some(evt) = rx.recv().await {
-                    println!("{evt:?}");
-                    match evt {
-                        ProjectorCommand::VideoEnded => {
-                            println!("a");
+            // meters, volume and warp follow the projector selected in the list
+            let playlist_model_clone = playlist_model.clone();
+            let volume_level: LevelBar = builder.object("volume_level").unwrap();
+            let audio_device_options_clone = audio_device_options.clone();
+            let meters = Rc::new(meters::Meters::new(builder.object("meters_box").unwrap()));
+            let meters_clone = meters.clone();
+            let silence_label: Label = builder.object("silence_label").unwrap();
+            let warp_editor_clone = warp_editor.clone();
+            let syncing_warp_clone = syncing_warp.clone();
+            let warp_columns_spin_clone = warp_columns_spin.clone();
+            let warp_rows_spin_clone = warp_rows_spin.clone();
+            let snapshot_picture: Picture = builder.object("snapshot_picture").unwrap();
+            let preview_stats_label: Label = builder.object("preview_stats_label").unwrap();
+            let preview_stats = Rc::new(RefCell::new(preview::PreviewStats::default()));
+            let preview_stats_clone = preview_stats.clone();
+            let projectors_weak = Rc::downgrade(&projectors);
+            projectors.set_event_handler(move |index, evt| {
+                let Some(projectors) = projectors_weak.upgrade() else {
+                    return;
+                };
+                let selected = index == projectors.selected();
+                match evt {
+                    ProjectorCommand::VideoEnded => {
+                        projectors.set_now_playing(index, "");
+                        if selected {
+                            meters_clone.clear();
+                        }
+                    }
+                    ProjectorCommand::ProbeResult {
+                        path,
+                        audio_tracks,
+                        subtitle_tracks,
+                        loudness,
+                    } => {
+                        playlist::set_tracks(
+                            &playlist_model_clone,
+                            &path,
+                            &audio_tracks,
+                            &subtitle_tracks,
+                        );
+                        if let Some(lufs) = loudness {
+                            playlist::set_loudness(&playlist_model_clone, &path, 0, lufs);
                         }
-                        _ => {}
                     }
+                    ProjectorCommand::Loudness {
+                        path,
+                        audio_track,
+                        lufs,
+                    } => {
+                        playlist::set_loudness(&playlist_model_clone, &path, audio_track, lufs);
+                    }
+                    ProjectorCommand::AudioDevices(devices) => {
+                        playlist::set_audio_devices(&audio_device_options_clone, &devices);
+                    }
+                    ProjectorCommand::AudioLevels(levels) if selected => {
+                        meters_clone.update(&levels);
+                    }
+                    ProjectorCommand::SilenceWarning(silent) if selected => {
+                        silence_label.set_visible(silent);
+                    }
+                    ProjectorCommand::VolumeLevel(level) if selected => {
+                        volume_level.set_value(level.min(volume_level.max_value()));
+                    }
+                    ProjectorCommand::Warp(warp) if selected => {
+                        syncing_warp_clone.set(true);
+                        warp_columns_spin_clone.set_value(warp.columns as f64);
+                        warp_rows_spin_clone.set_value(warp.rows as f64);
+                        syncing_warp_clone.set(false);
+                        warp_editor_clone.set_warp(warp);
+                    }
+                    ProjectorCommand::SnapshotImage { data, .. } if selected => {
+                        preview::show_image(&snapshot_picture, &data);
+                    }
+                    ProjectorCommand::PreviewFrame {
+                        width,
+                        height,
+                        captured_at,
+                        data,
+                    } if selected => {
+                        preview::show_image(&snapshot_picture, &data);
+                        let stats = preview_stats_clone.borrow_mut().frame(width, height, captured_at);
+                        preview_stats_label.set_text(&stats);
+                    }
+                    ProjectorCommand::ClockTime(time) => {
+                        projectors.clock_time(index, time);
+                    }
+                    _ => {}
                 }
             });
+            projectors.send(0, ProjectorCommand::ListAudioDevices);
+            projectors.send(0, ProjectorCommand::GetWarp);
 
             if let Some(add_button) = builder.object::<Button>("add_source_button") {
                 let playlist_model_clone = playlist_model.clone();
                 let window_clone = window_clone.clone();
+                let projectors = projectors.clone();
 
                 add_button.connect_clicked(move |_| {
                     let dialog = FileChooserNative::new(
@@ -84,18 +205,17 @@ async fn main() {
                     );
 
                     let playlist_model_inner = playlist_model_clone.clone();
+                    let projectors = projectors.clone();
 
                     dialog.connect_response(move |dialog, response| {
                         if response == ResponseType::Accept {
                             if let Some(file) = dialog.file() {
                                 if let Some(path) = file.path() {
-                                    playlist_model_inner.set(
-                                        &playlist_model_inner.append(),
-                                        &[
-                                            (0, &path.display().to_string()),
-                                            (1, &format!("{:?}", Skip::VideoEnd)),
-                                        ],
-                                    );
+                                    let path = path.display().to_string();
+                                    playlist::append_source(&playlist_model_inner, &path);
+
+                                    // the first projector answers with the tracks to choose from
+                                    projectors.send(0, ProjectorCommand::Probe { path });
                                 }
                             }
                         }
@@ -180,21 +300,122 @@ async fn main() {
                 }
             });
 
+            // track choices, switched live when the row is the one playing
+            let audio_renderer: CellRendererCombo = builder.object("audio_renderer").unwrap();
+            let playlist_model_clone = playlist_model.clone();
+            let playlist_list_clone = playlist_list.clone();
+            let projectors_clone = projectors.clone();
+            audio_renderer.connect_edited(move |_, path, new_text| {
+                if let Some(iter) = playlist_model_clone.iter(&path) {
+                    playlist_model_clone.set(&iter, &[(playlist::COL_AUDIO, &new_text)]);
+
+                    if playlist_list_clone.selection().iter_is_selected(&iter) {
+                        let outputs =
+                            playlist::text(&playlist_model_clone, &iter, playlist::COL_OUTPUTS);
+                        projectors_clone.send_to(
+                            &projectors_clone.targets(&outputs),
+                            ProjectorCommand::SelectAudioTrack(
+                                Track::index_from_label(new_text).unwrap_or(0),
+                            ),
+                        );
+                    }
+                }
+            });
+
+            let subtitle_renderer: CellRendererCombo =
+                builder.object("subtitle_renderer").unwrap();
+            let playlist_model_clone = playlist_model.clone();
+            let playlist_list_clone = playlist_list.clone();
+            let projectors_clone = projectors.clone();
+            subtitle_renderer.connect_edited(move |_, path, new_text| {
+                if let Some(iter) = playlist_model_clone.iter(&path) {
+                    playlist_model_clone.set(&iter, &[(playlist::COL_SUBTITLE, &new_text)]);
+
+                    if playlist_list_clone.selection().iter_is_selected(&iter) {
+                        let outputs =
+                            playlist::text(&playlist_model_clone, &iter, playlist::COL_OUTPUTS);
+                        projectors_clone.send_to(
+                            &projectors_clone.targets(&outputs),
+                            ProjectorCommand::SelectSubtitleTrack(Track::index_from_label(new_text)),
+                        );
+                    }
+                }
+            });
+
+            let audio_device_renderer: CellRendererCombo =
+                builder.object("audio_device_renderer").unwrap();
+            audio_device_renderer.set_property("model", &audio_device_options);
+            audio_device_renderer.set_property("text-column", &0);
+
+            let playlist_model_clone = playlist_model.clone();
+            audio_device_renderer.connect_edited(move |_, path, new_text| {
+                if let Some(iter) = playlist_model_clone.iter(&path) {
+                    playlist_model_clone.set(&iter, &[(playlist::COL_AUDIO_DEVICE, &new_text)]);
+                }
+            });
+
+            // combo columns with fixed choices
+            for (renderer_id, column, choices) in [
+                ("deinterlace_renderer", playlist::COL_DEINTERLACE, &playlist::DEINTERLACE_CHOICES[..]),
+                ("rotation_renderer", playlist::COL_ROTATION, &playlist::ROTATION_CHOICES[..]),
+                ("background_renderer", playlist::COL_BACKGROUND, &playlist::BACKGROUND_CHOICES[..]),
+            ] {
+                let renderer: CellRendererCombo = builder.object(renderer_id).unwrap();
+                renderer.set_property("model", &playlist::choice_options(choices));
+                renderer.set_property("text-column", &0);
+
+                let playlist_model_clone = playlist_model.clone();
+                renderer.connect_edited(move |_, path, new_text| {
+                    if let Some(iter) = playlist_model_clone.iter(&path) {
+                        playlist_model_clone.set(&iter, &[(column, &new_text)]);
+                    }
+                });
+            }
+
+            // scale modes can also be typed, e.g. to adjust the custom zoom and pan
+            let scale_mode_renderer: CellRendererCombo =
+                builder.object("scale_mode_renderer").unwrap();
+            scale_mode_renderer
+                .set_property("model", &playlist::choice_options(&playlist::SCALE_MODE_CHOICES));
+            scale_mode_renderer.set_property("text-column", &0);
+
+            let playlist_model_clone = playlist_model.clone();
+            scale_mode_renderer.connect_edited(move |_, path, new_text| {
+                if !playlist::is_scale_mode(new_text) {
+                    return;
+                }
+                if let Some(iter) = playlist_model_clone.iter(&path) {
+                    playlist_model_clone.set(&iter, &[(playlist::COL_SCALE_MODE, &new_text.trim())]);
+                }
+            });
+
+            // numeric columns, edits that don't parse are dropped
+            for (renderer_id, column) in [
+                ("volume_renderer", playlist::COL_VOLUME),
+                ("fade_in_renderer", playlist::COL_FADE_IN),
+                ("fade_out_renderer", playlist::COL_FADE_OUT),
+            ] {
+                let renderer: CellRendererText = builder.object(renderer_id).unwrap();
+                let playlist_model_clone = playlist_model.clone();
+                renderer.connect_edited(move |_, path, new_text| {
+                    if playlist::parse_non_negative(new_text).is_none() {
+                        return;
+                    }
+                    if let Some(iter) = playlist_model_clone.iter(&path) {
+                        playlist_model_clone.set(&iter, &[(column, &new_text.trim())]);
+                    }
+                });
+            }
+
             // controls
             if let Some(play_button) = builder.object::<Button>("play_button") {
                 let playlist_model_clone = playlist_model.clone();
                 let playlist_list_clone = playlist_list.clone();
-                let projector_client = projector_client.clone();
+                let projectors = projectors.clone();
 
                 play_button.connect_clicked(move |_| {
                     if let Some(iter) = playlist_model_clone.iter_first() {
-                        let path = playlist_model_clone.get_value(&iter, 0).get().unwrap();
-                        let skip = playlist_model_clone.get_value(&iter, 1).get().unwrap();
-
-                        if let Some(projector_client) = projector_client.borrow().as_ref() {
-                            let _ = projector_client
-                                .send_command(ProjectorCommand::Start { path, skip });
-                        }
+                        start_item(&projectors, &playlist_model_clone, &iter);
 
                         playlist_list_clone.selection().select_iter(&iter);
                     }
@@ -202,7 +423,7 @@ async fn main() {
             };
 
             if let Some(next_button) = builder.object::<Button>("next_button") {
-                let projector_client = projector_client.clone();
+                let projectors = projectors.clone();
 
                 let playlist_model_clone = playlist_model.clone();
                 let playlist_list_clone = playlist_list.clone();
@@ -211,13 +432,7 @@ async fn main() {
                     if let Some((_, iter)) = playlist_list_clone.selection().selected() {
                         let mut next_iter = iter.clone();
                         if playlist_model_clone.iter_next(&mut next_iter) {
-                            let path = playlist_model_clone.get_value(&next_iter, 0).get().unwrap();
-                            let skip = playlist_model_clone.get_value(&iter, 1).get().unwrap();
-
-                            if let Some(projector_client) = projector_client.borrow().as_ref() {
-                                let _ = projector_client
-                                    .send_command(ProjectorCommand::Start { path, skip });
-                            }
+                            start_item(&projectors, &playlist_model_clone, &next_iter);
 
                             playlist_list_clone.selection().select_iter(&next_iter);
                         }
@@ -225,7 +440,7 @@ async fn main() {
                 });
             };
             if let Some(prev_button) = builder.object::<Button>("prev_button") {
-                let projector_client = projector_client.clone();
+                let projectors = projectors.clone();
 
                 let playlist_model_clone = playlist_model.clone();
                 let playlist_list_clone = playlist_list.clone();
@@ -234,19 +449,196 @@ async fn main() {
                     if let Some((_, iter)) = playlist_list_clone.selection().selected() {
                         let mut prev_iter = iter.clone();
                         if playlist_model_clone.iter_previous(&mut prev_iter) {
-                            let path = playlist_model_clone.get_value(&prev_iter, 0).get().unwrap();
-                            let skip = playlist_model_clone.get_value(&iter, 1).get().unwrap();
-
-                            if let Some(projector_client) = projector_client.borrow().as_ref() {
-                                let _ = projector_client
-                                    .send_command(ProjectorCommand::Start { path, skip });
-                            }
+                            start_item(&projectors, &playlist_model_clone, &prev_iter);
 
                             playlist_list_clone.selection().select_iter(&prev_iter);
                         }
                     }
                 });
             };
+
+            if let Some(subtitles_button) = builder.object::<ToggleButton>("subtitles_button") {
+                let projectors = projectors.clone();
+
+                subtitles_button.connect_toggled(move |button| {
+                    projectors.send_all(ProjectorCommand::ShowSubtitles(button.is_active()));
+                });
+            };
+
+            if let Some(master_volume_scale) = builder.object::<Scale>("master_volume_scale") {
+                let projectors = projectors.clone();
+
+                master_volume_scale.connect_value_changed(move |scale| {
+                    projectors.send_all(ProjectorCommand::SetMasterVolume(scale.value()));
+                });
+            };
+
+            if let Some(mute_button) = builder.object::<ToggleButton>("mute_button") {
+                let projectors = projectors.clone();
+
+                mute_button.connect_toggled(move |button| {
+                    projectors.send_all(ProjectorCommand::SetMute(button.is_active()));
+                });
+            };
+
+            // live placement of the current item
+            let scale_mode_dropdown: DropDown = builder.object("scale_mode_dropdown").unwrap();
+            let zoom_spin: SpinButton = builder.object("zoom_spin").unwrap();
+            let pan_x_spin: SpinButton = builder.object("pan_x_spin").unwrap();
+            let pan_y_spin: SpinButton = builder.object("pan_y_spin").unwrap();
+            let send_scale_mode = {
+                let projectors = projectors.clone();
+                let (mode, zoom, pan_x, pan_y) = (
+                    scale_mode_dropdown.clone(),
+                    zoom_spin.clone(),
+                    pan_x_spin.clone(),
+                    pan_y_spin.clone(),
+                );
+                Rc::new(move || {
+                    projectors.send_all(ProjectorCommand::SetScaleMode(live_scale_mode(
+                        &mode, &zoom, &pan_x, &pan_y,
+                    )));
+                })
+            };
+            let send = send_scale_mode.clone();
+            scale_mode_dropdown.connect_selected_notify(move |_| send());
+            for spin in [&zoom_spin, &pan_x_spin, &pan_y_spin] {
+                let send = send_scale_mode.clone();
+                spin.connect_value_changed(move |_| send());
+            }
+
+            if let Some(rotate_button) = builder.object::<Button>("rotate_button") {
+                let projectors = projectors.clone();
+                let rotation = Rc::new(Cell::new(0u32));
+
+                rotate_button.connect_clicked(move |_| {
+                    rotation.set((rotation.get() + 90) % 360);
+                    projectors.send_all(ProjectorCommand::SetRotation(rotation.get()));
+                });
+            };
+
+            for spin in [&warp_columns_spin, &warp_rows_spin] {
+                let warp_editor = warp_editor.clone();
+                let syncing_warp = syncing_warp.clone();
+                let (columns, rows) = (warp_columns_spin.clone(), warp_rows_spin.clone());
+                spin.connect_value_changed(move |_| {
+                    if !syncing_warp.get() {
+                        warp_editor.set_mesh_size(columns.value() as u32, rows.value() as u32);
+                    }
+                });
+            }
+
+            if let Some(reset_warp_button) = builder.object::<Button>("reset_warp_button") {
+                let warp_editor = warp_editor.clone();
+                reset_warp_button.connect_clicked(move |_| warp_editor.reset());
+            }
+
+            if let Some(test_grid_button) = builder.object::<ToggleButton>("test_grid_button") {
+                let projectors = projectors.clone();
+
+                test_grid_button.connect_toggled(move |button| {
+                    projectors.send(
+                        projectors.selected(),
+                        ProjectorCommand::ShowTestGrid(button.is_active()),
+                    );
+                });
+            };
+
+            if let Some(snapshot_button) = builder.object::<Button>("snapshot_button") {
+                let projectors = projectors.clone();
+
+                snapshot_button.connect_clicked(move |_| {
+                    projectors.send(
+                        projectors.selected(),
+                        ProjectorCommand::Snapshot {
+                            max_width: SNAPSHOT_WIDTH,
+                            format: ImageFormat::Jpeg,
+                        },
+                    );
+                });
+            };
+
+            let live_preview_button: ToggleButton = builder.object("live_preview_button").unwrap();
+            {
+                let projectors = projectors.clone();
+                let preview_stats = preview_stats.clone();
+                let preview_stats_label: Label = builder.object("preview_stats_label").unwrap();
+
+                live_preview_button.connect_toggled(move |button| {
+                    if button.is_active() {
+                        projectors.send(
+                            projectors.selected(),
+                            ProjectorCommand::StartPreview {
+                                max_width: PREVIEW_WIDTH,
+                                fps: PREVIEW_FPS,
+                            },
+                        );
+                    } else {
+                        projectors.send(projectors.selected(), ProjectorCommand::StopPreview);
+                        preview_stats.borrow_mut().clear();
+                        preview_stats_label.set_text("");
+                    }
+                });
+            }
+
+            // projectors
+            let projector_list: TreeView = builder.object("projector_list").unwrap();
+            {
+                let projectors = projectors.clone();
+                let meters = meters.clone();
+                let live_preview_button = live_preview_button.clone();
+                projector_list.selection().connect_changed(move |selection| {
+                    let Some((model, iter)) = selection.selected() else {
+                        return;
+                    };
+                    let index = model.path(&iter).indices()[0] as usize;
+                    // the live preview follows the selection
+                    if live_preview_button.is_active() && index != projectors.selected() {
+                        projectors.send(projectors.selected(), ProjectorCommand::StopPreview);
+                        projectors.send(
+                            index,
+                            ProjectorCommand::StartPreview {
+                                max_width: PREVIEW_WIDTH,
+                                fps: PREVIEW_FPS,
+                            },
+                        );
+                        preview_stats.borrow_mut().clear();
+                    }
+                    projectors.set_selected(index);
+                    meters.clear();
+                    projectors.send(index, ProjectorCommand::GetWarp);
+                });
+            }
+
+            if let Some(add_projector_button) = builder.object::<Button>("add_projector_button") {
+                let url_field = builder.object::<Editable>("projector_url_entry").unwrap();
+                let projectors = projectors.clone();
+
+                add_projector_button.connect_clicked(move |_| {
+                    let url = url_field.text();
+                    if !url.trim().is_empty() {
+                        projectors.add(url.trim());
+                        url_field.set_text("");
+                    }
+                });
+            }
+
+            let outputs_renderer: CellRendererCombo = builder.object("outputs_renderer").unwrap();
+            outputs_renderer.set_property("model", projectors.output_options());
+            outputs_renderer.set_property("text-column", &0);
+
+            let playlist_model_clone = playlist_model.clone();
+            outputs_renderer.connect_edited(move |_, path, new_text| {
+                if let Some(iter) = playlist_model_clone.iter(&path) {
+                    playlist_model_clone.set(&iter, &[(playlist::COL_OUTPUTS, &new_text.trim())]);
+                }
+            });
+
+            let projectors_clone = projectors.clone();
+            glib::timeout_add_local(Duration::from_millis(500), move || {
+                projectors_clone.refresh_status();
+                glib::ControlFlow::Continue
+            });
         });
 
         window.present();
@@ -254,3 +646,33 @@ async fn main() {
 
     application.run();
 }
+
+/// Starts the row on the projectors named in its Outputs column
+fn start_item(projectors: &projectors::Projectors, model: &ListStore, iter: &TreeIter) {
+    let outputs = playlist::text(model, iter, playlist::COL_OUTPUTS);
+    projectors.start(
+        &projectors.targets(&outputs),
+        playlist::start_command(model, iter),
+        &playlist::title(model, iter),
+    );
+}
+
+/// Order of the items in `scale_mode_dropdown`, zoom and pan only apply to custom
+fn live_scale_mode(
+    mode: &DropDown,
+    zoom: &SpinButton,
+    pan_x: &SpinButton,
+    pan_y: &SpinButton,
+) -> ScaleMode {
+    match mode.selected() {
+        1 => ScaleMode::Fill,
+        2 => ScaleMode::Stretch,
+        3 => ScaleMode::Native,
+        4 => ScaleMode::Custom {
+            zoom: zoom.value() as f32,
+            pan_x: pan_x.value() as f32,
+            pan_y: pan_y.value() as f32,
+        },
+        _ => ScaleMode::Fit,
+    }
+}
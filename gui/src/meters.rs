@@ -0,0 +1,83 @@
+use gtk4::prelude::BoxExt;
+use gtk4::{LevelBar, Orientation};
+use shared::AudioLevels;
+use std::cell::RefCell;
+
+// Bottom of the meter scale in dBFS
+const METER_FLOOR_DB: f64 = -60.0;
+
+/// Two level bars per audio channel, the thick one showing the RMS level and the thin
+/// one above it the peak level
+pub struct Meters {
+    container: gtk4::Box,
+    channels: RefCell<Vec<ChannelMeter>>,
+}
+
+struct ChannelMeter {
+    row: gtk4::Box,
+    peak: LevelBar,
+    rms: LevelBar,
+}
+
+impl ChannelMeter {
+    fn new() -> Self {
+        let row = gtk4::Box::new(Orientation::Vertical, 1);
+        let peak = level_bar(4);
+        let rms = level_bar(10);
+        row.append(&peak);
+        row.append(&rms);
+        ChannelMeter { row, peak, rms }
+    }
+}
+
+fn level_bar(height: i32) -> LevelBar {
+    LevelBar::builder()
+        .orientation(Orientation::Horizontal)
+        .width_request(120)
+        .height_request(height)
+        .min_value(0.0)
+        .max_value(1.0)
+        .build()
+}
+
+impl Meters {
+    pub fn new(container: gtk4::Box) -> Self {
+        Meters {
+            container,
+            channels: RefCell::new(Vec::new()),
+        }
+    }
+
+    pub fn update(&self, levels: &AudioLevels) {
+        let mut channels = self.channels.borrow_mut();
+
+        // the channel count changes with the item
+        while channels.len() > levels.peak.len() {
+            if let Some(channel) = channels.pop() {
+                self.container.remove(&channel.row);
+            }
+        }
+        while channels.len() < levels.peak.len() {
+            let channel = ChannelMeter::new();
+            self.container.append(&channel.row);
+            channels.push(channel);
+        }
+
+        for (i, channel) in channels.iter().enumerate() {
+            channel.peak.set_value(db_to_fraction(levels.peak[i]));
+            let rms = levels.rms.get(i).copied().unwrap_or(METER_FLOOR_DB);
+            channel.rms.set_value(db_to_fraction(rms));
+        }
+    }
+
+    pub fn clear(&self) {
+        for channel in self.channels.borrow().iter() {
+            channel.peak.set_value(0.0);
+            channel.rms.set_value(0.0);
+        }
+    }
+}
+
+fn db_to_fraction(db: f64) -> f64 {
+    ((db - METER_FLOOR_DB) / -METER_FLOOR_DB).clamp(0.0, 1.0)
+}
@@ -0,0 +1,68 @@
+use gtk4::prelude::MediaStreamExt;
+use gtk4::{Label, MediaFile, MediaStream, Video};
+use std::cell::RefCell;
+use std::path::Path;
+
+/// Plays the selected playlist item on this machine, to check a clip and look for
+/// in and out points without putting it on the big screen
+pub struct LocalPlayer {
+    video: Video,
+    position_label: Label,
+    path: RefCell<String>,
+}
+
+impl LocalPlayer {
+    pub fn new(video: Video, position_label: Label) -> Self {
+        LocalPlayer {
+            video,
+            position_label,
+            path: RefCell::new(String::new()),
+        }
+    }
+
+    /// Loads `path` paused, the video's own controls play and scrub it
+    pub fn load(&self, path: &str) {
+        if *self.path.borrow() == path {
+            return;
+        }
+        *self.path.borrow_mut() = path.to_string();
+
+        // items may be paths on the projector rather than on this machine
+        if !Path::new(path).is_file() {
+            self.video.set_media_stream(None::<&MediaStream>);
+            self.position_label.set_text("Not found on this machine");
+            return;
+        }
+
+        let media = MediaFile::for_filename(path);
+        // the operator's speakers may be heard in the room
+        media.set_muted(true);
+        let label = self.position_label.clone();
+        media.connect_timestamp_notify(move |media| label.set_text(&position_text(media)));
+        let label = self.position_label.clone();
+        media.connect_duration_notify(move |media| label.set_text(&position_text(media)));
+        let label = self.position_label.clone();
+        media.connect_error_notify(move |media| {
+            if let Some(err) = media.error() {
+                label.set_text(&format!("Can't play: {}", err));
+            }
+        });
+
+        self.position_label.set_text("");
+        self.video.set_media_stream(Some(&media));
+    }
+}
+
+fn position_text(media: &MediaFile) -> String {
+    format!(
+        "{} / {}",
+        format_time(media.timestamp()),
+        format_time(media.duration())
+    )
+}
+
+/// Microseconds as minutes, seconds and milliseconds
+fn format_time(micros: i64) -> String {
+    let millis = micros.max(0) / 1000;
+    format!("{}:{:02}.{:03}", millis / 60_000, millis / 1000 % 60, millis % 1000)
+}
@@ -0,0 +1,193 @@
+use gtk4::prelude::{StaticType, TreeModelExt, TreeModelExtManual};
+use crate::projectors::ALL_OUTPUTS;
+use gtk4::{ListStore, TreeIter};
+use shared::{Background, ItemOptions, ProjectorCommand, ScaleMode, Skip, Track};
+
+// Columns of `playlist_model`, keep in sync with main_window.xml
+pub const COL_PATH: u32 = 0;
+pub const COL_SKIP: u32 = 1;
+pub const COL_AUDIO: u32 = 2;
+pub const COL_AUDIO_OPTIONS: u32 = 3;
+pub const COL_SUBTITLE: u32 = 4;
+pub const COL_SUBTITLE_OPTIONS: u32 = 5;
+pub const COL_VOLUME: u32 = 6;
+pub const COL_FADE_IN: u32 = 7;
+pub const COL_FADE_OUT: u32 = 8;
+pub const COL_AUDIO_DEVICE: u32 = 9;
+pub const COL_LOUDNESS: u32 = 10;
+pub const COL_DEINTERLACE: u32 = 11;
+pub const COL_SCALE_MODE: u32 = 12;
+pub const COL_ROTATION: u32 = 13;
+pub const COL_BACKGROUND: u32 = 14;
+pub const COL_OUTPUTS: u32 = 15;
+
+pub const DEFAULT_AUDIO: &str = "Default";
+pub const SUBTITLES_OFF: &str = "Off";
+pub const PROJECTOR_DEFAULT_DEVICE: &str = "Projector default";
+pub const DEINTERLACE_CHOICES: [&str; 3] = ["Auto", "On", "Off"];
+// "Default" leaves the choice to the projector's config, the custom entry is a template
+// to edit: zoom relative to Fit, then the pan as a fraction of the output size
+pub const SCALE_MODE_CHOICES: [&str; 6] =
+    ["Default", "Fit", "Fill", "Stretch", "Native", "custom:1,0,0"];
+pub const ROTATION_CHOICES: [&str; 5] = ["Default", "0", "90", "180", "270"];
+// anything else typed into the column is taken as an image path
+pub const BACKGROUND_CHOICES: [&str; 3] = ["Default", "Black", "Blur"];
+
+pub fn append_source(model: &ListStore, path: &str) -> TreeIter {
+    let iter = model.append();
+    model.set(
+        &iter,
+        &[
+            (COL_PATH, &path),
+            (COL_SKIP, &format!("{:?}", Skip::VideoEnd)),
+            (COL_AUDIO, &DEFAULT_AUDIO),
+            (COL_AUDIO_OPTIONS, &track_options(DEFAULT_AUDIO, &[])),
+            (COL_SUBTITLE, &SUBTITLES_OFF),
+            (COL_SUBTITLE_OPTIONS, &track_options(SUBTITLES_OFF, &[])),
+            (COL_VOLUME, &"1.0"),
+            (COL_FADE_IN, &"0"),
+            (COL_FADE_OUT, &"0"),
+            (COL_AUDIO_DEVICE, &PROJECTOR_DEFAULT_DEVICE),
+            (COL_LOUDNESS, &""),
+            (COL_DEINTERLACE, &DEINTERLACE_CHOICES[0]),
+            (COL_SCALE_MODE, &SCALE_MODE_CHOICES[0]),
+            (COL_ROTATION, &ROTATION_CHOICES[0]),
+            (COL_BACKGROUND, &BACKGROUND_CHOICES[0]),
+            (COL_OUTPUTS, &ALL_OUTPUTS),
+        ],
+    );
+    iter
+}
+
+/// Fills the track choices of every row playing `path` with the projector's probe result
+pub fn set_tracks(model: &ListStore, path: &str, audio_tracks: &[Track], subtitle_tracks: &[Track]) {
+    let Some(iter) = model.iter_first() else {
+        return;
+    };
+
+    loop {
+        if text(model, &iter, COL_PATH) == path {
+            model.set(
+                &iter,
+                &[
+                    (COL_AUDIO_OPTIONS, &track_options(DEFAULT_AUDIO, audio_tracks)),
+                    (COL_SUBTITLE_OPTIONS, &track_options(SUBTITLES_OFF, subtitle_tracks)),
+                ],
+            );
+        }
+
+        if !model.iter_next(&iter) {
+            break;
+        }
+    }
+}
+
+/// Shows the loudness on every row playing `audio_track` of `path`
+pub fn set_loudness(model: &ListStore, path: &str, audio_track: u32, lufs: f64) {
+    let Some(iter) = model.iter_first() else {
+        return;
+    };
+
+    loop {
+        let row_track = item_options(model, &iter).audio_track.unwrap_or(0);
+        if text(model, &iter, COL_PATH) == path && row_track == audio_track {
+            model.set(&iter, &[(COL_LOUDNESS, &format!("{:.1} LUFS", lufs))]);
+        }
+
+        if !model.iter_next(&iter) {
+            break;
+        }
+    }
+}
+
+pub fn item_options(model: &ListStore, iter: &TreeIter) -> ItemOptions {
+    ItemOptions {
+        audio_track: Track::index_from_label(&text(model, iter, COL_AUDIO)),
+        subtitle_track: Track::index_from_label(&text(model, iter, COL_SUBTITLE)),
+        volume: text(model, iter, COL_VOLUME).parse().ok(),
+        fade_in_ms: seconds_to_ms(&text(model, iter, COL_FADE_IN)),
+        fade_out_ms: seconds_to_ms(&text(model, iter, COL_FADE_OUT)),
+        audio_device: Some(text(model, iter, COL_AUDIO_DEVICE))
+            .filter(|d| d != PROJECTOR_DEFAULT_DEVICE),
+        deinterlace: match text(model, iter, COL_DEINTERLACE).as_str() {
+            "On" => Some(true),
+            "Off" => Some(false),
+            _ => None,
+        },
+        scale_mode: ScaleMode::from_string(&text(model, iter, COL_SCALE_MODE).trim().to_lowercase()),
+        rotation: text(model, iter, COL_ROTATION).parse().ok(),
+        background: background(&text(model, iter, COL_BACKGROUND)),
+        cue: false,
+    }
+}
+
+fn background(s: &str) -> Option<Background> {
+    match s.trim() {
+        "" | "Default" => None,
+        "Black" => Some(Background::Black),
+        "Blur" => Some(Background::Blur),
+        path => Some(Background::Image(path.to_string())),
+    }
+}
+
+pub fn start_command(model: &ListStore, iter: &TreeIter) -> ProjectorCommand {
+    ProjectorCommand::Start {
+        path: text(model, iter, COL_PATH),
+        skip: text(model, iter, COL_SKIP),
+        options: item_options(model, iter),
+    }
+}
+
+/// File name of the row, shown as what a projector is playing
+pub fn title(model: &ListStore, iter: &TreeIter) -> String {
+    let path = text(model, iter, COL_PATH);
+    std::path::Path::new(&path)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or(path)
+}
+
+pub fn text(model: &ListStore, iter: &TreeIter, column: u32) -> String {
+    model.get::<String>(iter, column as i32)
+}
+
+/// Accepts the default and anything `ScaleMode` parses, used to validate typed scale modes
+pub fn is_scale_mode(s: &str) -> bool {
+    s.trim() == SCALE_MODE_CHOICES[0] || ScaleMode::try_from(s.trim().to_lowercase()).is_ok()
+}
+
+/// Accepts non-negative numbers only, used to validate the numeric columns
+pub fn parse_non_negative(s: &str) -> Option<f64> {
+    s.trim().parse::<f64>().ok().filter(|v| *v >= 0.0)
+}
+
+fn seconds_to_ms(s: &str) -> u32 {
+    parse_non_negative(s).map(|secs| (secs * 1000.0) as u32).unwrap_or(0)
+}
+
+/// Choices for the output column, shared by every row
+pub fn set_audio_devices(options: &ListStore, devices: &[String]) {
+    options.clear();
+    options.set(&options.append(), &[(0, &PROJECTOR_DEFAULT_DEVICE)]);
+    for device in devices {
+        options.set(&options.append(), &[(0, device)]);
+    }
+}
+
+/// Model for the combo columns with a fixed set of choices
+pub fn choice_options(choices: &[&str]) -> ListStore {
+    let options = ListStore::new(&[String::static_type()]);
+    for choice in choices {
+        options.set(&options.append(), &[(0, choice)]);
+    }
+    options
+}
+
+fn track_options(first: &str, tracks: &[Track]) -> ListStore {
+    let options = ListStore::new(&[String::static_type()]);
+    options.set(&options.append(), &[(0, &first)]);
+    for track in tracks {
+        options.set(&options.append(), &[(0, &track.label())]);
+    }
+    options
+}
@@ -0,0 +1,50 @@
+use gtk4::{gdk, gdk_pixbuf, gio, glib, Picture};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Shows a PNG or JPEG sent by the projector
+pub fn show_image(picture: &Picture, data: &[u8]) {
+    let stream = gio::MemoryInputStream::from_bytes(&glib::Bytes::from(data));
+    match gdk_pixbuf::Pixbuf::from_stream(&stream, None::<&gio::Cancellable>) {
+        Ok(pixbuf) => picture.set_paintable(Some(&gdk::Texture::for_pixbuf(&pixbuf))),
+        Err(err) => eprintln!("Failed to load snapshot: {}", err),
+    }
+}
+
+/// Frame rate and latency of the live preview
+#[derive(Default)]
+pub struct PreviewStats {
+    // arrivals within the last second
+    arrivals: VecDeque<Instant>,
+}
+
+impl PreviewStats {
+    /// Records a frame and describes the stream. The latency compares the projector's
+    /// clock with ours, so it is only right when both machines keep the same time
+    pub fn frame(&mut self, width: u32, height: u32, captured_at: u64) -> String {
+        let now = Instant::now();
+        self.arrivals.push_back(now);
+        while self
+            .arrivals
+            .front()
+            .is_some_and(|arrival| now.duration_since(*arrival) > Duration::from_secs(1))
+        {
+            self.arrivals.pop_front();
+        }
+
+        let received_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_millis() as i64);
+        format!(
+            "{}x{}, {} fps, {} ms",
+            width,
+            height,
+            self.arrivals.len(),
+            received_at - captured_at as i64
+        )
+    }
+
+    pub fn clear(&mut self) {
+        self.arrivals.clear();
+    }
+}
@@ -0,0 +1,367 @@
+use gtk4::prelude::{StaticType, TreeModelExt};
+use gtk4::{glib, ListStore};
+use shared::client::ProjectorClient;
+use shared::ProjectorCommand;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+// Columns of `projector_model`, keep in sync with main_window.xml
+pub const COL_NUMBER: u32 = 0;
+pub const COL_URL: u32 = 1;
+pub const COL_STATUS: u32 = 2;
+pub const COL_NOW_PLAYING: u32 = 3;
+
+// playlist items go to every projector unless they name some by number
+pub const ALL_OUTPUTS: &str = "All";
+// time the projectors get to preroll an item that starts on several of them
+const SYNC_START_DELAY_NS: u64 = 500_000_000;
+// projectors that don't report their clock by then are started without sync
+const CLOCK_REPLY_TIMEOUT: Duration = Duration::from_secs(2);
+// on top of the round trip, for clocks read at slightly different moments
+const CLOCK_TOLERANCE: Duration = Duration::from_millis(20);
+
+type EventHandler = Rc<dyn Fn(usize, ProjectorCommand)>;
+
+/// Every projector the GUI is connected to, in the order of `projector_model`. They are
+/// numbered from 1 in the playlist's Outputs column
+pub struct Projectors {
+    clients: Rc<RefCell<Vec<ProjectorClient>>>,
+    model: ListStore,
+    /// Choices of the Outputs column
+    output_options: ListStore,
+    handler: Rc<RefCell<Option<EventHandler>>>,
+    /// The projector whose meters and warp are shown
+    selected: Cell<usize>,
+    sync_start: SyncStart,
+}
+
+impl Projectors {
+    pub fn new(model: ListStore) -> Self {
+        let output_options = ListStore::new(&[String::static_type()]);
+        output_options.set(&output_options.append(), &[(0, &ALL_OUTPUTS)]);
+
+        let clients = Rc::new(RefCell::new(Vec::new()));
+        Projectors {
+            clients: clients.clone(),
+            model,
+            output_options,
+            handler: Rc::new(RefCell::new(None)),
+            selected: Cell::new(0),
+            sync_start: SyncStart {
+                clients,
+                pending: Rc::new(RefCell::new(None)),
+            },
+        }
+    }
+
+    pub fn output_options(&self) -> &ListStore {
+        &self.output_options
+    }
+
+    /// Events of every projector go to `handler`, along with the projector's index
+    pub fn set_event_handler(&self, handler: impl Fn(usize, ProjectorCommand) + 'static) {
+        *self.handler.borrow_mut() = Some(Rc::new(handler));
+    }
+
+    pub fn add(&self, url: &str) -> usize {
+        let client = ProjectorClient::new(url);
+        let mut rx = client.subscribe();
+        let mut clients = self.clients.borrow_mut();
+        let index = clients.len();
+        clients.push(client);
+
+        let number = (index + 1).to_string();
+        self.model.set(
+            &self.model.append(),
+            &[
+                (COL_NUMBER, &number),
+                (COL_URL, &url),
+                (COL_STATUS, &"Connecting"),
+                (COL_NOW_PLAYING, &""),
+            ],
+        );
+        self.output_options
+            .set(&self.output_options.append(), &[(0, &number)]);
+
+        // events touch the widgets, so handle them on the GTK main loop
+        let handler = self.handler.clone();
+        glib::spawn_future_local(async move {
+            while let Some(evt) = rx.recv().await {
+                // images would flood the log
+                if !matches!(
+                    evt,
+                    ProjectorCommand::SnapshotImage { .. } | ProjectorCommand::PreviewFrame { .. }
+                ) {
+                    println!("{index}: {evt:?}");
+                }
+                let handler = handler.borrow().clone();
+                if let Some(handler) = handler {
+                    handler(index, evt);
+                }
+            }
+        });
+
+        index
+    }
+
+    pub fn count(&self) -> usize {
+        self.clients.borrow().len()
+    }
+
+    pub fn send(&self, index: usize, cmd: ProjectorCommand) {
+        if let Some(client) = self.clients.borrow().get(index) {
+            client.send_command(cmd);
+        }
+    }
+
+    pub fn send_to(&self, targets: &[usize], cmd: ProjectorCommand) {
+        for index in targets {
+            self.send(*index, cmd.clone());
+        }
+    }
+
+    pub fn send_all(&self, cmd: ProjectorCommand) {
+        for client in self.clients.borrow().iter() {
+            client.send_command(cmd.clone());
+        }
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected.get()
+    }
+
+    pub fn set_selected(&self, index: usize) {
+        self.selected.set(index);
+    }
+
+    /// Projectors named by an Outputs cell, e.g. `All` or `1, 3`
+    pub fn targets(&self, outputs: &str) -> Vec<usize> {
+        let count = self.count();
+        if outputs.trim().eq_ignore_ascii_case(ALL_OUTPUTS) {
+            return (0..count).collect();
+        }
+
+        let mut targets: Vec<usize> = outputs
+            .split(',')
+            .filter_map(|number| number.trim().parse::<usize>().ok())
+            .filter(|number| (1..=count).contains(number))
+            .map(|number| number - 1)
+            .collect();
+        targets.sort();
+        targets.dedup();
+        targets
+    }
+
+    /// Starts an item on its targets. Several targets that share a clock get it cued
+    /// and are started together on that clock, see `SyncStart`
+    pub fn start(&self, targets: &[usize], start: ProjectorCommand, title: &str) {
+        for index in targets {
+            self.set_now_playing(*index, title);
+        }
+
+        match (targets, start) {
+            ([], _) => {}
+            ([index], start) => self.send(*index, start),
+            (_, start @ ProjectorCommand::Start { .. }) => {
+                self.sync_start.begin(targets.to_vec(), start)
+            }
+            (_, other) => self.send_to(targets, other),
+        }
+    }
+
+    /// Handles a `ClockTime` reply of a synced start
+    pub fn clock_time(&self, index: usize, time: u64) {
+        self.sync_start.clock_time(index, time);
+    }
+
+    pub fn set_now_playing(&self, index: usize, title: &str) {
+        if let Some(iter) = self.model.iter_nth_child(None, index as i32) {
+            self.model.set(&iter, &[(COL_NOW_PLAYING, &title)]);
+        }
+    }
+
+    /// Copies the connection state of every projector into the list
+    pub fn refresh_status(&self) {
+        for (index, client) in self.clients.borrow().iter().enumerate() {
+            if let Some(iter) = self.model.iter_nth_child(None, index as i32) {
+                self.model
+                    .set(&iter, &[(COL_STATUS, &client.status().to_string())]);
+            }
+        }
+    }
+}
+
+/// Starts an item on several projectors at once. Every target is first asked for its
+/// clock's time to check that they share one: projectors on one machine do, as do a
+/// `[sync]` leader and its followers, anything else is started one by one. The item is
+/// then cued everywhere and started on a base time a moment after the first projector's
+#[derive(Clone)]
+struct SyncStart {
+    clients: Rc<RefCell<Vec<ProjectorClient>>>,
+    pending: Rc<RefCell<Option<PendingStart>>>,
+}
+
+struct PendingStart {
+    targets: Vec<usize>,
+    /// The item's `Start`, without `cue`
+    start: ProjectorCommand,
+    /// Also tells a timeout whether it is still about this start
+    asked_at: Instant,
+    phase: StartPhase,
+}
+
+enum StartPhase {
+    /// Waiting for every target's clock time, with when it arrived
+    Probing(Vec<(usize, u64, Instant)>),
+    /// Cued on every target, waiting for the time of `targets[source]`
+    Cued { source: usize },
+}
+
+impl SyncStart {
+    fn send(&self, index: usize, cmd: ProjectorCommand) {
+        if let Some(client) = self.clients.borrow().get(index) {
+            client.send_command(cmd);
+        }
+    }
+
+    fn begin(&self, targets: Vec<usize>, start: ProjectorCommand) {
+        for index in &targets {
+            self.send(*index, ProjectorCommand::GetClockTime);
+        }
+        self.wait_for(PendingStart {
+            targets,
+            start,
+            asked_at: Instant::now(),
+            phase: StartPhase::Probing(Vec::new()),
+        });
+    }
+
+    fn wait_for(&self, pending: PendingStart) {
+        let asked_at = pending.asked_at;
+        *self.pending.borrow_mut() = Some(pending);
+        let sync_start = self.clone();
+        glib::timeout_add_local_once(CLOCK_REPLY_TIMEOUT, move || sync_start.timed_out(asked_at));
+    }
+
+    fn clock_time(&self, index: usize, time: u64) {
+        let Some(mut pending) = self.pending.borrow_mut().take() else {
+            return;
+        };
+
+        match &mut pending.phase {
+            StartPhase::Probing(replies) => {
+                if pending.targets.contains(&index) && !replies.iter().any(|r| r.0 == index) {
+                    replies.push((index, time, Instant::now()));
+                }
+                if replies.len() == pending.targets.len() {
+                    self.cue(pending);
+                    return;
+                }
+            }
+            StartPhase::Cued { source } if pending.targets[*source] == index => {
+                for index in &pending.targets {
+                    self.send(
+                        *index,
+                        ProjectorCommand::StartAt {
+                            base_time: time + SYNC_START_DELAY_NS,
+                        },
+                    );
+                }
+                return;
+            }
+            StartPhase::Cued { .. } => {}
+        }
+        *self.pending.borrow_mut() = Some(pending);
+    }
+
+    /// Cues the item on the projectors that reported a shared clock, the others just
+    /// start it
+    fn cue(&self, pending: PendingStart) {
+        let StartPhase::Probing(replies) = &pending.phase else {
+            return;
+        };
+        let answered: Vec<usize> = replies.iter().map(|r| r.0).collect();
+        for index in pending.targets.iter().filter(|i| !answered.contains(i)) {
+            self.send(*index, pending.start.clone());
+        }
+
+        let shared = answered.len() >= 2 && shares_clock(pending.asked_at, replies);
+        if !shared {
+            if answered.len() >= 2 {
+                eprintln!("The projectors don't share a clock, starting them one by one");
+            }
+            for index in &answered {
+                self.send(*index, pending.start.clone());
+            }
+            return;
+        }
+
+        let mut cued = pending.start.clone();
+        if let ProjectorCommand::Start { options, .. } = &mut cued {
+            options.cue = true;
+        }
+        for index in &answered {
+            self.send(*index, cued.clone());
+        }
+        // asked after the cue, so the base time leaves the whole delay for prerolling
+        self.send(answered[0], ProjectorCommand::GetClockTime);
+        self.wait_for(PendingStart {
+            targets: answered,
+            start: pending.start,
+            asked_at: Instant::now(),
+            phase: StartPhase::Cued { source: 0 },
+        });
+    }
+
+    fn timed_out(&self, asked_at: Instant) {
+        let pending = {
+            let mut slot = self.pending.borrow_mut();
+            if slot.as_ref().map(|p| p.asked_at) != Some(asked_at) {
+                return;
+            }
+            slot.take()
+        };
+        let Some(pending) = pending else {
+            return;
+        };
+
+        match pending.phase {
+            StartPhase::Probing(_) => {
+                eprintln!("Not every projector reported its clock in time");
+                self.cue(pending);
+            }
+            // the others are cued on the same clock, any of them can give the time
+            StartPhase::Cued { source } if source + 1 < pending.targets.len() => {
+                self.send(pending.targets[source + 1], ProjectorCommand::GetClockTime);
+                self.wait_for(PendingStart {
+                    asked_at: Instant::now(),
+                    phase: StartPhase::Cued { source: source + 1 },
+                    ..pending
+                });
+            }
+            StartPhase::Cued { .. } => {
+                eprintln!("No projector reported its clock, starting them one by one");
+                for index in &pending.targets {
+                    self.send(*index, pending.start.clone());
+                }
+            }
+        }
+    }
+}
+
+/// Whether clock times fit a single clock. Each was read somewhere between `asked_at`
+/// and its arrival, so on one clock they agree up to the slowest round trip
+fn shares_clock(asked_at: Instant, replies: &[(usize, u64, Instant)]) -> bool {
+    let offsets: Vec<i128> = replies
+        .iter()
+        .map(|(_, time, arrived)| *time as i128 - (*arrived - asked_at).as_nanos() as i128)
+        .collect();
+    let round_trip = replies
+        .iter()
+        .map(|(_, _, arrived)| *arrived - asked_at)
+        .max()
+        .unwrap_or_default();
+    let spread = offsets.iter().max().unwrap_or(&0) - offsets.iter().min().unwrap_or(&0);
+    spread <= (round_trip + CLOCK_TOLERANCE).as_nanos() as i128
+}
@@ -0,0 +1,228 @@
+use gtk4::prelude::{DrawingAreaExtManual, GestureDragExt, WidgetExt};
+use gtk4::{cairo, DrawingArea, GestureDrag};
+use shared::Warp;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+// the preview doesn't know the projector's resolution, most are 16:9
+const OUTPUT_ASPECT: f64 = 16.0 / 9.0;
+const MARGIN: f64 = 12.0;
+const HANDLE_RADIUS: f64 = 6.0;
+const PREVIEW_GRID: u32 = 8;
+
+#[derive(Clone, Copy)]
+enum Handle {
+    Corner(usize),
+    Mesh(u32, u32),
+}
+
+/// Preview of the projector output with drag handles for the corners and mesh points
+pub struct WarpEditor {
+    area: DrawingArea,
+    warp: Rc<RefCell<Warp>>,
+    on_change: Rc<dyn Fn(&Warp)>,
+}
+
+impl WarpEditor {
+    /// `on_change` runs on every edit, including each step of a drag
+    pub fn new(area: DrawingArea, on_change: impl Fn(&Warp) + 'static) -> Self {
+        let warp = Rc::new(RefCell::new(Warp::default()));
+        let on_change: Rc<dyn Fn(&Warp)> = Rc::new(on_change);
+
+        let warp_clone = warp.clone();
+        area.set_draw_func(move |_, cr, width, height| {
+            draw(cr, &warp_clone.borrow(), width as f64, height as f64);
+        });
+
+        let drag = GestureDrag::new();
+        let dragging = Rc::new(Cell::new(None::<(Handle, f64, f64)>));
+
+        let (warp_clone, dragging_clone, area_clone) = (warp.clone(), dragging.clone(), area.clone());
+        drag.connect_drag_begin(move |_, x, y| {
+            let frame = output_frame(area_clone.width() as f64, area_clone.height() as f64);
+            dragging_clone.set(
+                nearest_handle(&warp_clone.borrow(), &frame, x, y).map(|handle| (handle, x, y)),
+            );
+        });
+
+        let (warp_clone, dragging_clone, area_clone, on_change_clone) =
+            (warp.clone(), dragging.clone(), area.clone(), on_change.clone());
+        drag.connect_drag_update(move |_, offset_x, offset_y| {
+            let Some((handle, start_x, start_y)) = dragging_clone.get() else {
+                return;
+            };
+            let frame = output_frame(area_clone.width() as f64, area_clone.height() as f64);
+            let (x, y) = frame.to_output(start_x + offset_x, start_y + offset_y);
+
+            let mut warp = warp_clone.borrow_mut();
+            match handle {
+                Handle::Corner(i) => warp.corners[i] = (x, y),
+                Handle::Mesh(column, row) => {
+                    let (u, v) = warp.unpin(x, y);
+                    let index = (row * (warp.columns + 1) + column) as usize;
+                    let home = (
+                        column as f32 / warp.columns as f32,
+                        row as f32 / warp.rows as f32,
+                    );
+                    if let Some(point) = warp.mesh.get_mut(index) {
+                        *point = (u - home.0, v - home.1);
+                    }
+                }
+            }
+            area_clone.queue_draw();
+            on_change_clone(&warp);
+        });
+
+        let dragging_clone = dragging.clone();
+        drag.connect_drag_end(move |_, _, _| dragging_clone.set(None));
+        area.add_controller(drag);
+
+        WarpEditor {
+            area,
+            warp,
+            on_change,
+        }
+    }
+
+    /// Shows the projector's warp without sending it back
+    pub fn set_warp(&self, warp: Warp) {
+        *self.warp.borrow_mut() = warp;
+        self.area.queue_draw();
+    }
+
+    pub fn set_mesh_size(&self, columns: u32, rows: u32) {
+        let mut warp = self.warp.borrow_mut();
+        if (warp.columns, warp.rows) == (columns, rows) {
+            return;
+        }
+        warp.set_mesh_size(columns, rows);
+        self.changed(&warp);
+    }
+
+    pub fn reset(&self) {
+        let mut warp = self.warp.borrow_mut();
+        let (columns, rows) = (warp.columns, warp.rows);
+        *warp = Warp::default();
+        warp.set_mesh_size(columns, rows);
+        self.changed(&warp);
+    }
+
+    fn changed(&self, warp: &Warp) {
+        self.area.queue_draw();
+        (self.on_change)(warp);
+    }
+}
+
+/// The output's rectangle inside the drawing area
+struct Frame {
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+}
+
+impl Frame {
+    fn to_area(&self, (x, y): (f32, f32)) -> (f64, f64) {
+        (self.x + x as f64 * self.width, self.y + y as f64 * self.height)
+    }
+
+    fn to_output(&self, x: f64, y: f64) -> (f32, f32) {
+        (
+            ((x - self.x) / self.width).clamp(0.0, 1.0) as f32,
+            ((y - self.y) / self.height).clamp(0.0, 1.0) as f32,
+        )
+    }
+}
+
+fn output_frame(width: f64, height: f64) -> Frame {
+    let (available_width, available_height) =
+        ((width - 2.0 * MARGIN).max(1.0), (height - 2.0 * MARGIN).max(1.0));
+    let (frame_width, frame_height) = if available_width / available_height > OUTPUT_ASPECT {
+        (available_height * OUTPUT_ASPECT, available_height)
+    } else {
+        (available_width, available_width / OUTPUT_ASPECT)
+    };
+
+    Frame {
+        x: (width - frame_width) / 2.0,
+        y: (height - frame_height) / 2.0,
+        width: frame_width,
+        height: frame_height,
+    }
+}
+
+fn handles(warp: &Warp) -> Vec<(Handle, (f32, f32))> {
+    let mut handles: Vec<_> = (0..4)
+        .map(|i| (Handle::Corner(i), warp.corners[i]))
+        .collect();
+    if !warp.has_mesh() {
+        return handles;
+    }
+    for row in 0..=warp.rows {
+        for column in 0..=warp.columns {
+            // the corner mesh points sit under the corner handles
+            if (row == 0 || row == warp.rows) && (column == 0 || column == warp.columns) {
+                continue;
+            }
+            let (u, v) = (
+                column as f32 / warp.columns as f32,
+                row as f32 / warp.rows as f32,
+            );
+            handles.push((Handle::Mesh(column, row), warp.map(u, v)));
+        }
+    }
+    handles
+}
+
+fn nearest_handle(warp: &Warp, frame: &Frame, x: f64, y: f64) -> Option<Handle> {
+    handles(warp)
+        .into_iter()
+        .map(|(handle, point)| {
+            let (hx, hy) = frame.to_area(point);
+            (handle, (hx - x).hypot(hy - y))
+        })
+        .filter(|(_, distance)| *distance <= HANDLE_RADIUS * 2.0)
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(handle, _)| handle)
+}
+
+fn draw(cr: &cairo::Context, warp: &Warp, width: f64, height: f64) {
+    let frame = output_frame(width, height);
+
+    cr.set_source_rgb(0.15, 0.15, 0.15);
+    let _ = cr.paint();
+    cr.set_source_rgb(0.0, 0.0, 0.0);
+    cr.rectangle(frame.x, frame.y, frame.width, frame.height);
+    let _ = cr.fill();
+
+    // the warped image, drawn as a grid
+    cr.set_source_rgb(0.6, 0.6, 0.6);
+    cr.set_line_width(1.0);
+    let steps = PREVIEW_GRID * 4;
+    for i in 0..=PREVIEW_GRID {
+        let t = i as f32 / PREVIEW_GRID as f32;
+        for vertical in [true, false] {
+            for j in 0..=steps {
+                let s = j as f32 / steps as f32;
+                let (u, v) = if vertical { (t, s) } else { (s, t) };
+                let (x, y) = frame.to_area(warp.map(u, v));
+                if j == 0 {
+                    cr.move_to(x, y);
+                } else {
+                    cr.line_to(x, y);
+                }
+            }
+        }
+    }
+    let _ = cr.stroke();
+
+    for (handle, point) in handles(warp) {
+        let (x, y) = frame.to_area(point);
+        match handle {
+            Handle::Corner(_) => cr.set_source_rgb(0.9, 0.3, 0.2),
+            Handle::Mesh(..) => cr.set_source_rgb(0.3, 0.6, 0.9),
+        }
+        cr.arc(x, y, HANDLE_RADIUS, 0.0, std::f64::consts::TAU);
+        let _ = cr.fill();
+    }
+}
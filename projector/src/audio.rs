@@ -0,0 +1,291 @@
+use anyhow::{anyhow, Error};
+use gstreamer as gst;
+use gstreamer::glib;
+use gstreamer::prelude::*;
+use log::warn;
+use shared::AudioLevels;
+use std::time::{Duration, Instant};
+
+/// Device name that discards audio while keeping real-time playback, for headless machines
+pub const NULL_DEVICE: &str = "null";
+
+/// Peaks below this count as silence
+pub const SILENCE_THRESHOLD_DB: f64 = -60.0;
+const SILENCE_WARNING_DELAY: Duration = Duration::from_secs(3);
+const LEVEL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The element the item volume, fades and master volume are applied on
+pub fn make_volume(initial: f64) -> Result<gst::Element, Error> {
+    Ok(gst::ElementFactory::make("volume")
+        .name("volume")
+        .property("volume", initial)
+        .build()?)
+}
+
+/// Meters what actually goes out, so it sits after the volume element
+pub fn make_level() -> Result<gst::Element, Error> {
+    Ok(gst::ElementFactory::make("level")
+        .name("level")
+        .property("interval", LEVEL_INTERVAL.as_nanos() as u64)
+        .property("post-messages", true)
+        .build()?)
+}
+
+/// Reads the element message posted by the `level` element
+pub fn levels_from_structure(s: &gst::StructureRef) -> Option<AudioLevels> {
+    if !s.has_name("level") {
+        return None;
+    }
+
+    let values = |field: &str| -> Option<Vec<f64>> {
+        let array = s.get::<glib::ValueArray>(field).ok()?;
+        Some(array.iter().filter_map(|v| v.get::<f64>().ok()).collect())
+    };
+
+    Some(AudioLevels {
+        peak: values("peak")?,
+        rms: values("rms")?,
+    })
+}
+
+/// Raises a warning once audio that should be playing has been silent for a few seconds
+#[derive(Default)]
+pub struct SilenceDetector {
+    silent_since: Option<Instant>,
+    warning: bool,
+}
+
+impl SilenceDetector {
+    /// Returns the new warning state when it changes
+    pub fn update(&mut self, expecting_audio: bool, audible: bool) -> Option<bool> {
+        let now = Instant::now();
+        if expecting_audio && !audible {
+            self.silent_since.get_or_insert(now);
+        } else {
+            self.silent_since = None;
+        }
+
+        let warning = self
+            .silent_since
+            .is_some_and(|since| now - since >= SILENCE_WARNING_DELAY);
+        if warning == self.warning {
+            return None;
+        }
+
+        self.warning = warning;
+        Some(warning)
+    }
+}
+
+/// Where the audio branch ends up and how source channels are mapped onto it
+pub struct AudioOutput {
+    /// Display name of a `gst::DeviceMonitor` device, `None` uses `autoaudiosink`
+    pub device: Option<String>,
+    /// Output channel `i` plays source channel `channel_map[i]`, empty keeps the layout
+    pub channel_map: Vec<u32>,
+}
+
+pub fn build_audio_branch(
+    volume: gst::Element,
+    level: gst::Element,
+    output: &AudioOutput,
+) -> Result<Vec<gst::Element>, Error> {
+    let convert = gst::ElementFactory::make("audioconvert").build()?;
+    let mut branch = vec![gst::ElementFactory::make("queue").build()?, convert.clone()];
+
+    if !output.channel_map.is_empty() {
+        // the matrix is built for the negotiated layout, which changes with the audio track
+        let channel_map = output.channel_map.clone();
+        let weak_convert = convert.downgrade();
+        convert
+            .static_pad("sink")
+            .ok_or_else(|| anyhow!("audioconvert has no sink pad"))?
+            .add_probe(gst::PadProbeType::EVENT_DOWNSTREAM, move |_, info| {
+                if let Some(gst::EventView::Caps(caps)) = info.event().map(|e| e.view()) {
+                    let channels = caps
+                        .caps()
+                        .structure(0)
+                        .and_then(|s| s.get::<i32>("channels").ok())
+                        .unwrap_or(0);
+                    if let Some(convert) = weak_convert.upgrade() {
+                        convert.set_property("mix-matrix", mix_matrix(&channel_map, channels as u32));
+                    }
+                }
+                gst::PadProbeReturn::Ok
+            });
+        let channels = output.channel_map.len() as u32;
+        let mut caps = gst::Caps::builder("audio/x-raw").field("channels", channels as i32);
+        // unpositioned multichannel caps don't negotiate with most sinks
+        if channels > 2 {
+            caps = caps.field("channel-mask", gst::Bitmask::new(fallback_channel_mask(channels)));
+        }
+        branch.push(
+            gst::ElementFactory::make("capsfilter")
+                .property("caps", caps.build())
+                .build()?,
+        );
+    }
+
+    branch.push(volume);
+    branch.push(level);
+    branch.push(gst::ElementFactory::make("audioresample").build()?);
+    branch.push(make_audio_sink(output.device.as_deref())?);
+
+    Ok(branch)
+}
+
+/// Lists the display names of every audio sink the system offers
+pub fn list_audio_devices() -> Vec<String> {
+    let monitor = gst::DeviceMonitor::new();
+    monitor.add_filter(Some("Audio/Sink"), None);
+
+    if let Err(err) = monitor.start() {
+        warn!("Failed to start audio device monitor: {}", err);
+        return Vec::new();
+    }
+    let devices = monitor
+        .devices()
+        .iter()
+        .map(|d| d.display_name().to_string())
+        .collect();
+    monitor.stop();
+
+    devices
+}
+
+fn make_audio_sink(device: Option<&str>) -> Result<gst::Element, Error> {
+    match device {
+        None => Ok(gst::ElementFactory::make("autoaudiosink").build()?),
+        Some(NULL_DEVICE) => Ok(gst::ElementFactory::make("fakesink")
+            .property("sync", true)
+            .build()?),
+        // a missing device shouldn't take the item down with it
+        Some(name) => open_audio_device(name).or_else(|err| {
+            warn!("{}, playing on the default output", err);
+            Ok(gst::ElementFactory::make("autoaudiosink").build()?)
+        }),
+    }
+}
+
+fn open_audio_device(name: &str) -> Result<gst::Element, Error> {
+    let monitor = gst::DeviceMonitor::new();
+    monitor.add_filter(Some("Audio/Sink"), None);
+    monitor.start()?;
+    let device = monitor
+        .devices()
+        .into_iter()
+        .find(|d| d.display_name().as_str() == name);
+    monitor.stop();
+
+    device
+        .ok_or_else(|| anyhow!("Audio device '{}' not found", name))?
+        .create_element(None)
+        .map_err(|e| anyhow!("Failed to open audio device '{}': {}", name, e))
+}
+
+/// GStreamer's default layout for a channel count, as bits of GstAudioChannelPosition:
+/// FL FR FC LFE1 RL RR, plus RC for 6.1 and SL SR for 7.1
+fn fallback_channel_mask(channels: u32) -> u64 {
+    const FL: u64 = 1 << 0;
+    const FR: u64 = 1 << 1;
+    const FC: u64 = 1 << 2;
+    const LFE1: u64 = 1 << 3;
+    const RL: u64 = 1 << 4;
+    const RR: u64 = 1 << 5;
+    const RC: u64 = 1 << 8;
+    const SL: u64 = 1 << 10;
+    const SR: u64 = 1 << 11;
+
+    match channels {
+        2 => FL | FR,
+        3 => FL | FR | FC,
+        4 => FL | FR | RL | RR,
+        5 => FL | FR | FC | RL | RR,
+        6 => FL | FR | FC | LFE1 | RL | RR,
+        7 => FL | FR | FC | LFE1 | RL | RR | RC,
+        8 => FL | FR | FC | LFE1 | RL | RR | SL | SR,
+        _ => 0,
+    }
+}
+
+/// Builds audioconvert's `mix-matrix`, one row per output channel. A map naming channels
+/// the source doesn't have gives an empty matrix, leaving the mixing to audioconvert
+fn mix_matrix(channel_map: &[u32], source_channels: u32) -> gst::Array {
+    if let Some(missing) = channel_map.iter().find(|source| **source >= source_channels) {
+        warn!(
+            "channel_map uses source channel {}, but the audio has {} channels",
+            missing, source_channels
+        );
+        return gst::Array::new(std::iter::empty::<gst::Array>());
+    }
+    gst::Array::new(channel_map.iter().map(|source| {
+        gst::Array::new((0..source_channels).map(|i| if i == *source { 1.0f32 } else { 0.0f32 }))
+    }))
+}
+
+/// Gain multiplier for the fades at `position_ms`, linear ramps from and to silence
+pub fn fade_gain(position_ms: u64, duration_ms: u64, fade_in_ms: u32, fade_out_ms: u32) -> f64 {
+    let mut gain = 1.0;
+
+    if fade_in_ms > 0 && position_ms < fade_in_ms as u64 {
+        gain *= position_ms as f64 / fade_in_ms as f64;
+    }
+
+    let remaining_ms = duration_ms.saturating_sub(position_ms);
+    if fade_out_ms > 0 && remaining_ms < fade_out_ms as u64 {
+        gain *= remaining_ms as f64 / fade_out_ms as f64;
+    }
+
+    gain
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(matrix: &gst::Array) -> Vec<Vec<f32>> {
+        matrix
+            .iter()
+            .map(|row| {
+                row.get::<gst::Array>()
+                    .unwrap()
+                    .iter()
+                    .map(|v| v.get::<f32>().unwrap())
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn mix_matrix_routes_source_channels() {
+        gst::init().unwrap();
+
+        // swap the front pair of a 5.1 source and add a centre-only third output
+        let matrix = mix_matrix(&[1, 0, 2], 6);
+        assert_eq!(
+            rows(&matrix),
+            vec![
+                vec![0.0, 1.0, 0.0, 0.0, 0.0, 0.0],
+                vec![1.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+                vec![0.0, 0.0, 1.0, 0.0, 0.0, 0.0],
+            ]
+        );
+    }
+
+    #[test]
+    fn mix_matrix_is_empty_for_missing_channels() {
+        gst::init().unwrap();
+
+        assert!(mix_matrix(&[0, 2], 2).is_empty());
+    }
+
+    #[test]
+    fn fallback_masks_match_the_channel_count() {
+        for channels in 2..=8 {
+            assert_eq!(fallback_channel_mask(channels).count_ones(), channels);
+        }
+        // 5.1 has its LFE in the fourth position
+        assert_eq!(fallback_channel_mask(6), 0b11_1111);
+        assert_eq!(fallback_channel_mask(9), 0);
+    }
+}
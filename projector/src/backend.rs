@@ -0,0 +1,159 @@
+use crate::config::{ProjectorConfig, WindowConfig};
+use crate::video::RaylibVideo;
+use anyhow::{anyhow, Error};
+use gstreamer as gst;
+use gstreamer_video::{VideoFormat, VideoFrameRef, VideoInfo};
+use raylib::ffi;
+use raylib::prelude::PixelFormat;
+use shared::{ImageFormat, Monitor};
+use std::ffi::CString;
+
+/// Input the projector reacts to, whatever it renders on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackendEvent {
+    /// F3, shows the frame statistics
+    ToggleStats,
+}
+
+/// Everything on the output for one frame
+pub struct Scene<'a> {
+    pub video: Option<&'a mut RaylibVideo>,
+    pub config: &'a ProjectorConfig,
+    pub show_subtitles: bool,
+    pub show_stats: bool,
+    pub show_test_grid: bool,
+    /// Addresses for the idle screen, empty once a GUI started something
+    pub addresses: &'a [String],
+    /// Keeps the shown frame for `snapshot`, scaled to at most this width, 0 for full size
+    pub capture: Option<u32>,
+}
+
+/// What the projector renders on, a raylib window or memory when there is no display
+pub trait RenderBackend {
+    fn should_close(&self) -> bool;
+    /// Input since the last call
+    fn events(&mut self) -> Vec<BackendEvent>;
+    /// Output size in pixels
+    fn screen_size(&self) -> (i32, i32);
+    fn monitors(&self) -> Vec<Monitor>;
+    fn place_window(&mut self, window: &WindowConfig) -> anyhow::Result<()>;
+    /// Switches the display to a refresh rate suiting `fps` content, see `match_refresh`
+    fn match_refresh_rate(&mut self, fps: f64);
+    /// Composes the scene and shows it, paced by `[video] pacing`
+    fn present(&mut self, scene: Scene<'_>);
+    /// The frame kept by the last capturing `present`, warp and edge blending included
+    fn snapshot(&mut self) -> Option<Frame>;
+}
+
+/// An RGBA image in memory, rows tightly packed
+#[derive(Clone)]
+pub struct Frame {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+impl Frame {
+    pub fn black(width: u32, height: u32) -> Self {
+        let mut rgba = vec![0; width as usize * height as usize * 4];
+        rgba.chunks_exact_mut(4).for_each(|pixel| pixel[3] = 255);
+        Frame {
+            width,
+            height,
+            rgba,
+        }
+    }
+
+    /// Copies an RGBA sample, packing padded rows
+    pub fn from_sample(sample: &gst::Sample) -> Result<Self, Error> {
+        let caps = sample.caps().ok_or_else(|| anyhow!("Sample without caps"))?;
+        let info = VideoInfo::from_caps(caps)?;
+        if info.format() != VideoFormat::Rgba {
+            return Err(anyhow!("Expected RGBA frames, got {:?}", info.format()));
+        }
+        let buffer = sample.buffer().ok_or_else(|| anyhow!("Sample without buffer"))?;
+        let frame = VideoFrameRef::from_buffer_ref_readable(buffer, &info)?;
+
+        let row_bytes = info.width() as usize * 4;
+        let stride = frame.plane_stride()[0] as usize;
+        let rgba = frame
+            .plane_data(0)?
+            .chunks(stride)
+            .take(info.height() as usize)
+            .flat_map(|row| &row[..row_bytes])
+            .copied()
+            .collect();
+        Ok(Frame {
+            width: info.width(),
+            height: info.height(),
+            rgba,
+        })
+    }
+
+    /// Loads an image file with raylib, which needs no window for it
+    pub fn load(path: &str) -> Result<Self, Error> {
+        let c_path = CString::new(path)?;
+        // SAFETY: LoadImage returns an image we own or one without data, ImageFormat
+        // converts it in place, and it is freed once copied
+        unsafe {
+            let mut image = ffi::LoadImage(c_path.as_ptr());
+            if image.data.is_null() {
+                return Err(anyhow!("Failed to load image '{}'", path));
+            }
+            ffi::ImageFormat(&mut image, PixelFormat::PIXELFORMAT_UNCOMPRESSED_R8G8B8A8 as i32);
+            let frame = Frame::from_image(&image);
+            ffi::UnloadImage(image);
+            Ok(frame)
+        }
+    }
+
+    /// Copies an RGBA image raylib allocated, freeing it stays with the caller
+    ///
+    /// # Safety
+    /// `image` has to be a valid R8G8B8A8 image or have no data
+    pub unsafe fn from_image(image: &ffi::Image) -> Self {
+        let (width, height) = (image.width.max(0) as u32, image.height.max(0) as u32);
+        let len = width as usize * height as usize * 4;
+        let rgba = if image.data.is_null() {
+            vec![0; len]
+        } else {
+            // SAFETY: raylib's RGBA images hold width * height * 4 bytes
+            unsafe { std::slice::from_raw_parts(image.data as *const u8, len).to_vec() }
+        };
+        Frame {
+            width,
+            height,
+            rgba,
+        }
+    }
+
+    /// Size fitting into `max_width` with the same aspect ratio, 0 keeps the full size
+    pub fn scaled_size(&self, max_width: u32) -> (u32, u32) {
+        if max_width == 0 || max_width >= self.width {
+            return (self.width, self.height);
+        }
+        let height = (self.height as u64 * max_width as u64 / self.width as u64).max(1);
+        (max_width, height as u32)
+    }
+
+    /// Encodes the frame with GStreamer, scaled to `size`
+    pub fn encode(&self, format: ImageFormat, size: (u32, u32)) -> Result<Vec<u8>, Error> {
+        let info = VideoInfo::builder(VideoFormat::Rgba, self.width, self.height).build()?;
+        let buffer = gst::Buffer::from_mut_slice(self.rgba.clone());
+        let sample = gst::Sample::builder()
+            .buffer(&buffer)
+            .caps(&info.to_caps()?)
+            .build();
+
+        let caps = gst::Caps::builder(format.media_type())
+            .field("width", size.0 as i32)
+            .field("height", size.1 as i32)
+            .build();
+        let encoded = gstreamer_video::convert_sample(&sample, &caps, gst::ClockTime::from_seconds(5))
+            .map_err(|e| anyhow!("Failed to encode frame as {}: {}", format, e))?;
+        let buffer = encoded
+            .buffer()
+            .ok_or_else(|| anyhow!("Encoded sample without buffer"))?;
+        Ok(buffer.map_readable()?.to_vec())
+    }
+}
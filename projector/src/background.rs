@@ -0,0 +1,153 @@
+use crate::layout::place;
+use crate::video::RaylibVideo;
+use raylib::prelude::*;
+use shared::{Background, ScaleMode};
+
+// the frame is rendered this many times smaller before blurring, which does most of
+// the blurring for free
+const BLUR_DOWNSCALE: i32 = 8;
+const BLUR_TINT: Color = Color::new(110, 110, 110, 255);
+
+const BLUR_SHADER: &str = r#"
+#version 330
+in vec2 fragTexCoord;
+in vec4 fragColor;
+out vec4 finalColor;
+
+uniform sampler2D texture0;
+uniform vec2 texelSize;
+
+void main() {
+    vec4 sum = vec4(0.0);
+    float total = 0.0;
+    for (int x = -2; x <= 2; x++) {
+        for (int y = -2; y <= 2; y++) {
+            float weight = exp(-float(x * x + y * y) / 4.0);
+            sum += texture(texture0, fragTexCoord + vec2(x, y) * texelSize) * weight;
+            total += weight;
+        }
+    }
+    finalColor = vec4((sum / total).rgb, 1.0) * fragColor;
+}
+"#;
+
+/// Fills the output behind the video with a blurred copy of it or an image
+pub struct BackgroundRenderer {
+    blur_shader: Shader,
+    texel_size_loc: i32,
+    blur_target: Option<(RenderTexture2D, (i32, i32))>,
+    /// `None` texture when the image failed to load, so it isn't retried every frame
+    image: Option<(String, Option<Texture2D>)>,
+}
+
+impl BackgroundRenderer {
+    pub fn new(rl: &mut RaylibHandle, thread: &RaylibThread) -> Self {
+        let blur_shader = rl.load_shader_from_memory(thread, None, Some(BLUR_SHADER));
+        let texel_size_loc = blur_shader.get_shader_location("texelSize");
+
+        BackgroundRenderer {
+            blur_shader,
+            texel_size_loc,
+            blur_target: None,
+            image: None,
+        }
+    }
+
+    /// Loads what `background` needs, has to run before drawing starts
+    pub fn prepare(
+        &mut self,
+        rl: &mut RaylibHandle,
+        thread: &RaylibThread,
+        background: &Background,
+        screen: (i32, i32),
+    ) {
+        match background {
+            Background::Black => {}
+            Background::Blur => {
+                let size = (
+                    (screen.0 / BLUR_DOWNSCALE).max(1),
+                    (screen.1 / BLUR_DOWNSCALE).max(1),
+                );
+                if self.blur_target.as_ref().is_some_and(|(_, s)| *s == size) {
+                    return;
+                }
+
+                match rl.load_render_texture(thread, size.0 as u32, size.1 as u32) {
+                    Ok(target) => {
+                        let texel_size = Vector2::new(1.0 / size.0 as f32, 1.0 / size.1 as f32);
+                        self.blur_shader.set_shader_value(self.texel_size_loc, texel_size);
+                        self.blur_target = Some((target, size));
+                    }
+                    Err(err) => eprintln!("Failed to create blur target: {}", err),
+                }
+            }
+            Background::Image(path) => {
+                if self.image.as_ref().is_some_and(|(loaded, _)| loaded == path) {
+                    return;
+                }
+
+                let texture = rl
+                    .load_texture(thread, path)
+                    .map_err(|e| eprintln!("Failed to load background image {:?}: {}", path, e))
+                    .ok();
+                self.image = Some((path.clone(), texture));
+            }
+        }
+    }
+
+    /// Renders the video into the blur texture. Has its own texture mode, so it has to
+    /// run before anything else is drawn off-screen
+    pub fn render(&mut self, d: &mut RaylibDrawHandle, thread: &RaylibThread, video: &RaylibVideo) {
+        if video.background != Background::Blur {
+            return;
+        }
+        let Some((target, size)) = self.blur_target.as_mut() else {
+            return;
+        };
+
+        let mut t = d.begin_texture_mode(thread, target);
+        t.clear_background(Color::BLACK);
+        let fill = place(
+            ScaleMode::Fill,
+            video.rotation,
+            video.display_size(),
+            (size.0 as f32, size.1 as f32),
+        );
+        video.draw_frame(&mut t, &fill);
+    }
+
+    pub fn draw(&self, d: &mut RaylibDrawHandle, video: &RaylibVideo, screen: (f32, f32)) {
+        let dest = Rectangle::new(0.0, 0.0, screen.0, screen.1);
+
+        match &video.background {
+            Background::Black => {}
+            Background::Blur => {
+                let Some((target, size)) = self.blur_target.as_ref() else {
+                    return;
+                };
+
+                // render textures are stored upside down
+                let source = Rectangle::new(0.0, 0.0, size.0 as f32, -size.1 as f32);
+                let mut s = d.begin_shader_mode(&self.blur_shader);
+                s.draw_texture_pro(
+                    target.texture(),
+                    source,
+                    dest,
+                    Vector2::zero(),
+                    0.0,
+                    BLUR_TINT,
+                );
+            }
+            Background::Image(path) => {
+                let Some((_, Some(texture))) = self.image.as_ref().filter(|(p, _)| p == path) else {
+                    return;
+                };
+                let (width, height) = (texture.width as f32, texture.height as f32);
+                let fill = place(ScaleMode::Fill, 0, (width, height), screen);
+                let dest = Rectangle::new(fill.center.x, fill.center.y, fill.size.x, fill.size.y);
+                let source = Rectangle::new(0.0, 0.0, width, height);
+                d.draw_texture_pro(texture, source, dest, fill.size / 2.0, 0.0, Color::WHITE);
+            }
+        }
+    }
+}
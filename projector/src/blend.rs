@@ -0,0 +1,94 @@
+use crate::config::BlendConfig;
+use crate::frame::make_texture;
+use raylib::prelude::*;
+
+/// Darkens the soft edges of the output so that two overlapping projectors add up to
+/// an even image. The mask is multiplied over everything drawn before it
+pub struct EdgeBlend {
+    mask: Option<(Texture2D, (i32, i32))>,
+}
+
+impl EdgeBlend {
+    pub fn new() -> Self {
+        EdgeBlend { mask: None }
+    }
+
+    /// Builds the mask for the output size, has to run before drawing starts
+    pub fn prepare(
+        &mut self,
+        rl: &mut RaylibHandle,
+        thread: &RaylibThread,
+        config: &BlendConfig,
+        screen: (i32, i32),
+    ) {
+        if !config.has_edges() {
+            self.mask = None;
+            return;
+        }
+        if self.mask.as_ref().is_some_and(|(_, size)| *size == screen) {
+            return;
+        }
+
+        let (width, height) = (screen.0.max(1), screen.1.max(1));
+        let pixels = edge_mask(config, (width, height));
+
+        let format = PixelFormat::PIXELFORMAT_UNCOMPRESSED_GRAYSCALE;
+        let result = make_texture(rl, thread, width, height, format).and_then(|mut texture| {
+            texture.update_texture(&pixels)?;
+            Ok(texture)
+        });
+        match result {
+            Ok(texture) => self.mask = Some((texture, screen)),
+            Err(err) => eprintln!("Failed to create edge blend mask: {}", err),
+        }
+    }
+
+    pub fn draw(&self, d: &mut RaylibDrawHandle) {
+        if let Some((mask, _)) = &self.mask {
+            let mut b = d.begin_blend_mode(BlendMode::BLEND_MULTIPLIED);
+            b.draw_texture(mask, 0, 0, Color::WHITE);
+        }
+    }
+}
+
+/// Gain of every output pixel row by row, 255 where nothing is blended
+pub fn edge_mask(config: &BlendConfig, screen: (i32, i32)) -> Vec<u8> {
+    let columns = edge_gains(screen.0, config.left, config.right, config);
+    let rows = edge_gains(screen.1, config.top, config.bottom, config);
+    rows.iter()
+        .flat_map(|row| {
+            columns
+                .iter()
+                .map(move |column| (row * column * 255.0).round() as u8)
+        })
+        .collect()
+}
+
+/// Pixel gain along one axis of `length` pixels with soft edges at both ends
+fn edge_gains(length: i32, start: f32, end: f32, config: &BlendConfig) -> Vec<f32> {
+    (0..length)
+        .map(|i| {
+            let position = (i as f32 + 0.5) / length as f32;
+            let light = ramp(position / start, config.curve)
+                * ramp((1.0 - position) / end, config.curve);
+            // the projector turns pixel values into light through its gamma
+            light.powf(1.0 / config.gamma.max(0.1))
+        })
+        .collect()
+}
+
+/// Light output across a blend zone, `t` runs from 0 at the outer edge to 1 where the
+/// zone ends. Symmetric about the middle so both projectors add up to 1
+fn ramp(t: f32, curve: f32) -> f32 {
+    // also catches a zone width of 0, which divides to infinity
+    if t >= 1.0 {
+        return 1.0;
+    }
+    let t = t.max(0.0);
+    let curve = curve.max(0.1);
+    if t < 0.5 {
+        0.5 * (2.0 * t).powf(curve)
+    } else {
+        1.0 - 0.5 * (2.0 * (1.0 - t)).powf(curve)
+    }
+}
@@ -0,0 +1,135 @@
+use crate::backend::Frame;
+use raylib::ffi;
+use raylib::prelude::*;
+
+// glBlitFramebuffer's mask for the colour attachment, rlgl passes it through as is
+const COLOR_BUFFER_BIT: i32 = 0x4000;
+
+/// Reads back the shown frame. Scaled captures are drawn into a small render texture on
+/// the GPU first, so only the preview sized image crosses the bus
+pub struct ScreenCapture {
+    // a copy of the framebuffer, which can't be sampled directly
+    copy: Option<(RenderTexture2D, (i32, i32))>,
+    scaled: Option<(RenderTexture2D, (i32, i32))>,
+}
+
+impl ScreenCapture {
+    pub fn new() -> Self {
+        ScreenCapture {
+            copy: None,
+            scaled: None,
+        }
+    }
+
+    /// Creates the textures for a capture no wider than `max_width`, 0 for the full size.
+    /// Has to run before drawing starts
+    pub fn prepare(
+        &mut self,
+        rl: &mut RaylibHandle,
+        thread: &RaylibThread,
+        screen: (i32, i32),
+        max_width: u32,
+    ) {
+        let screen = (screen.0.max(1), screen.1.max(1));
+        if max_width == 0 || max_width as i32 >= screen.0 {
+            self.scaled = None;
+            return;
+        }
+        let height = (screen.1 as i64 * max_width as i64 / screen.0 as i64).max(1) as i32;
+        let scaled = (max_width as i32, height);
+
+        if !self.copy.as_ref().is_some_and(|(_, size)| *size == screen) {
+            self.copy = load_target(rl, thread, screen);
+        }
+        if !self.scaled.as_ref().is_some_and(|(_, size)| *size == scaled) {
+            self.scaled = load_target(rl, thread, scaled);
+        }
+    }
+
+    /// Reads back what was drawn this frame at the size given to `prepare`, has to run
+    /// before drawing ends
+    pub fn read(&self) -> Frame {
+        let (Some((copy, screen)), Some((scaled, size))) = (&self.copy, &self.scaled) else {
+            return read_screen();
+        };
+        let copy: ffi::RenderTexture2D = *copy.as_ref();
+        let scaled: ffi::RenderTexture2D = *scaled.as_ref();
+
+        // SAFETY: both targets are live render textures we own, the framebuffer bindings
+        // and blend state are restored before returning, and the image is freed once copied
+        unsafe {
+            ffi::rlDrawRenderBatchActive();
+            ffi::rlBindFramebuffer(ffi::RL_READ_FRAMEBUFFER, 0);
+            ffi::rlBindFramebuffer(ffi::RL_DRAW_FRAMEBUFFER, copy.id);
+            ffi::rlBlitFramebuffer(0, 0, screen.0, screen.1, 0, 0, screen.0, screen.1, COLOR_BUFFER_BIT);
+            ffi::rlDisableFramebuffer();
+
+            // the copy is bottom-up like the framebuffer, drawing it unflipped puts the
+            // top row first in the scaled texture's memory, the order images use
+            ffi::BeginTextureMode(scaled);
+            ffi::rlDisableColorBlend();
+            ffi::DrawTexturePro(
+                copy.texture,
+                ffi::Rectangle {
+                    x: 0.0,
+                    y: 0.0,
+                    width: screen.0 as f32,
+                    height: screen.1 as f32,
+                },
+                ffi::Rectangle {
+                    x: 0.0,
+                    y: 0.0,
+                    width: size.0 as f32,
+                    height: size.1 as f32,
+                },
+                ffi::Vector2 { x: 0.0, y: 0.0 },
+                0.0,
+                Color::WHITE.into(),
+            );
+            ffi::rlDrawRenderBatchActive();
+            ffi::rlEnableColorBlend();
+            ffi::EndTextureMode();
+
+            let image = ffi::LoadImageFromTexture(scaled.texture);
+            let mut frame = Frame::from_image(&image);
+            ffi::UnloadImage(image);
+            frame.rgba.chunks_exact_mut(4).for_each(|pixel| pixel[3] = 255);
+            frame
+        }
+    }
+}
+
+fn load_target(
+    rl: &mut RaylibHandle,
+    thread: &RaylibThread,
+    size: (i32, i32),
+) -> Option<(RenderTexture2D, (i32, i32))> {
+    match rl.load_render_texture(thread, size.0 as u32, size.1 as u32) {
+        Ok(target) => {
+            let texture: ffi::Texture2D = *target.texture().as_ref();
+            // SAFETY: plain raylib call on a texture we own
+            unsafe {
+                ffi::SetTextureFilter(texture, ffi::TextureFilter::TEXTURE_FILTER_BILINEAR as i32)
+            };
+            Some((target, size))
+        }
+        Err(err) => {
+            eprintln!("Failed to create capture target: {}", err);
+            None
+        }
+    }
+}
+
+/// Reads back the whole framebuffer
+fn read_screen() -> Frame {
+    // SAFETY: LoadImageFromScreen returns a tightly packed RGBA copy of the framebuffer,
+    // which is freed again once copied
+    unsafe {
+        // it reads the framebuffer as is, the warp and overlays may still be batched
+        ffi::rlDrawRenderBatchActive();
+        let image = ffi::LoadImageFromScreen();
+        let frame = Frame::from_image(&image);
+        ffi::UnloadImage(image);
+        frame
+    }
+}
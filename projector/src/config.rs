@@ -0,0 +1,411 @@
+use anyhow::anyhow;
+use crate::layout::Viewport;
+use raylib::prelude::Rectangle;
+use serde::{Deserialize, Serialize};
+use shared::{Background, ScaleMode, Warp};
+use std::path::Path;
+
+pub const DEFAULT_CONFIG_PATH: &str = "projector.toml";
+
+/// Per-projector settings, read from a TOML file at startup
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ProjectorConfig {
+    /// Address the GUI connects to, `0.0.0.0:8765` accepts remote GUIs
+    pub listen: String,
+    /// Where measured loudness and other probe results are cached
+    pub probe_cache: String,
+    pub video: VideoConfig,
+    pub audio: AudioConfig,
+    pub loudness: LoudnessConfig,
+    pub subtitles: SubtitleStyle,
+    pub window: WindowConfig,
+    /// Config files of more output windows, each one runs as a projector of its own
+    pub outputs: Vec<String>,
+    /// Keystone and mesh correction, adjusted from the GUI and saved back to the file
+    pub warp: Warp,
+    pub blend: BlendConfig,
+    pub wall: WallConfig,
+    pub sync: SyncConfig,
+    pub headless: HeadlessConfig,
+}
+
+impl Default for ProjectorConfig {
+    fn default() -> Self {
+        ProjectorConfig {
+            listen: "127.0.0.1:8765".to_string(),
+            probe_cache: "probe-cache.toml".to_string(),
+            video: VideoConfig::default(),
+            audio: AudioConfig::default(),
+            loudness: LoudnessConfig::default(),
+            subtitles: SubtitleStyle::default(),
+            window: WindowConfig::default(),
+            outputs: Vec::new(),
+            warp: Warp::default(),
+            blend: BlendConfig::default(),
+            wall: WallConfig::default(),
+            sync: SyncConfig::default(),
+            headless: HeadlessConfig::default(),
+        }
+    }
+}
+
+impl ProjectorConfig {
+    /// A missing file is not an error, every setting has a default
+    pub fn load(path: &Path) -> anyhow::Result<ProjectorConfig> {
+        if !path.exists() {
+            return Ok(ProjectorConfig::default());
+        }
+
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read config '{}': {}", path.display(), e))?;
+        toml::from_str(&content)
+            .map_err(|e| anyhow!("Failed to parse config '{}': {}", path.display(), e))
+    }
+
+    /// Port part of `listen`, shown on the idle screen
+    pub fn listen_port(&self) -> &str {
+        self.listen.rsplit(':').next().unwrap_or_default()
+    }
+
+    /// Writes every setting back, comments in the file are not kept
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let content = toml::to_string(self)?;
+        std::fs::write(path, content)
+            .map_err(|e| anyhow!("Failed to write config '{}': {}", path.display(), e))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct VideoConfig {
+    /// Upload I420/NV12 frames as decoded and convert them in a shader, otherwise
+    /// frames are converted to RGBA on the CPU
+    pub yuv_upload: bool,
+    /// Decode-side scaling to the window size, saves CPU when the window is smaller
+    /// than the video
+    pub downscale: bool,
+    pub scale_quality: ScaleQuality,
+    /// Overrides the YUV matrix the source is tagged with
+    pub matrix: ColorMatrix,
+    /// Overrides the source's range, limited is 16-235
+    pub range: ColorRange,
+    /// Default placement, `fit`, `fill`, `stretch`, `native` or `custom:zoom,pan_x,pan_y`
+    pub scale_mode: ScaleMode,
+    /// Default clockwise rotation in 90° steps, for portrait screens
+    pub rotation: u32,
+    /// Default fill around the video, `black`, `blur` or `image:path`
+    pub background: Background,
+    pub pacing: FramePacing,
+    /// Render rate with `FramePacing::Fixed`, and while no video frames arrive
+    pub target_fps: u32,
+    pub vsync: bool,
+    /// Switches a fullscreen output to a refresh rate that is a multiple of the video's
+    /// frame rate, so every frame stays on screen equally long
+    pub match_refresh: bool,
+}
+
+impl Default for VideoConfig {
+    fn default() -> Self {
+        VideoConfig {
+            yuv_upload: true,
+            downscale: true,
+            scale_quality: ScaleQuality::Good,
+            matrix: ColorMatrix::Auto,
+            range: ColorRange::Auto,
+            scale_mode: ScaleMode::Fit,
+            rotation: 0,
+            background: Background::Black,
+            pacing: FramePacing::Content,
+            target_fps: 60,
+            vsync: true,
+            match_refresh: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScaleQuality {
+    /// Nearest neighbour, cheapest on old machines
+    Fast,
+    /// Bilinear
+    Good,
+    /// Lanczos
+    Best,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorMatrix {
+    /// As tagged, untagged SD is BT.601 and HD BT.709
+    Auto,
+    Bt601,
+    Bt709,
+    Bt2020,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorRange {
+    Auto,
+    Limited,
+    Full,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FramePacing {
+    /// Present each frame as soon as it is due on the pipeline clock
+    Content,
+    /// Redraw at `target_fps` no matter what plays
+    Fixed,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AudioConfig {
+    /// Master volume the projector starts with, 1.0 is unity gain
+    pub master_volume: f64,
+    /// Output device name as listed by the GUI, `"null"` discards audio
+    pub device: Option<String>,
+    /// Source channel for each output channel, e.g. `[1, 0]` swaps left and right
+    pub channel_map: Vec<u32>,
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        AudioConfig {
+            master_volume: 1.0,
+            device: None,
+            channel_map: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct LoudnessConfig {
+    /// Measure every item and adjust its gain to reach `target`
+    pub normalize: bool,
+    /// Target integrated loudness in LUFS, -23 is the EBU R128 level
+    pub target: f64,
+    /// Quiet items are never boosted by more than this
+    pub max_gain_db: f64,
+}
+
+impl Default for LoudnessConfig {
+    fn default() -> Self {
+        LoudnessConfig {
+            normalize: true,
+            target: -23.0,
+            max_gain_db: 12.0,
+        }
+    }
+}
+
+/// Edge blending, for projectors that overlap to form one wide image
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct BlendConfig {
+    /// Part of the source this projector shows, `[x, y, width, height]` as fractions
+    pub crop: [f32; 4],
+    /// Widths of the soft edges where the image overlaps a neighbour, as fractions of
+    /// the output
+    pub left: f32,
+    pub right: f32,
+    pub top: f32,
+    pub bottom: f32,
+    /// Of the projector, the falloff is made linear in light rather than pixel values
+    pub gamma: f32,
+    /// Shape of the falloff, 1 is a straight ramp and higher values flatten its ends
+    pub curve: f32,
+}
+
+impl Default for BlendConfig {
+    fn default() -> Self {
+        BlendConfig {
+            crop: [0.0, 0.0, 1.0, 1.0],
+            left: 0.0,
+            right: 0.0,
+            top: 0.0,
+            bottom: 0.0,
+            gamma: 2.2,
+            curve: 2.0,
+        }
+    }
+}
+
+impl BlendConfig {
+    /// The crop, clamped to the frame
+    pub fn crop(&self) -> Rectangle {
+        let [x, y, width, height] = self.crop;
+        let (x, y) = (x.clamp(0.0, 1.0), y.clamp(0.0, 1.0));
+        Rectangle::new(
+            x,
+            y,
+            width.clamp(0.01, 1.0 - x.min(0.99)),
+            height.clamp(0.01, 1.0 - y.min(0.99)),
+        )
+    }
+
+    pub fn has_edges(&self) -> bool {
+        [self.left, self.right, self.top, self.bottom]
+            .iter()
+            .any(|width| *width > 0.0)
+    }
+}
+
+/// Video wall tile, the output shows its part of a canvas spanning all screens
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct WallConfig {
+    /// Size of the whole wall in any unit, e.g. millimetres, unset disables the wall
+    pub canvas: Option<[f32; 2]>,
+    /// This screen's outer rectangle on the canvas, `[x, y, width, height]`
+    pub tile: [f32; 4],
+    /// Widths of the screen's bezels, `[left, top, right, bottom]`. The picture behind
+    /// them is hidden, so lines continue straight across the gaps
+    pub bezel: [f32; 4],
+}
+
+impl Default for WallConfig {
+    fn default() -> Self {
+        WallConfig {
+            canvas: None,
+            tile: [0.0, 0.0, 1.0, 1.0],
+            bezel: [0.0; 4],
+        }
+    }
+}
+
+impl WallConfig {
+    pub fn viewport(&self, screen: (f32, f32)) -> Viewport {
+        let Some([canvas_width, canvas_height]) = self.canvas else {
+            return Viewport::full(screen);
+        };
+
+        let [x, y, width, height] = self.tile;
+        let [left, top, right, bottom] = self.bezel;
+        let visible = [
+            x + left,
+            y + top,
+            width - left - right,
+            height - top - bottom,
+        ];
+        Viewport::tile((canvas_width, canvas_height), visible, screen)
+    }
+}
+
+/// Where the output window opens, F11 still toggles fullscreen
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct WindowConfig {
+    /// Index as listed at startup, the window opens where the system puts it when unset
+    pub monitor: Option<u32>,
+    pub fullscreen: bool,
+    /// Size when not fullscreen
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        WindowConfig {
+            monitor: None,
+            fullscreen: false,
+            width: 800,
+            height: 600,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncRole {
+    /// Cued items start on the system clock, enough for projectors on one machine
+    Off,
+    /// Serves the clock to the followers
+    Leader,
+    Follower,
+}
+
+/// Shared clock for starting items on several projectors at the same instant
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SyncConfig {
+    pub role: SyncRole,
+    /// Address of the leader, for followers
+    pub leader: String,
+    /// UDP port the leader serves its clock on
+    pub clock_port: u16,
+}
+
+impl Default for SyncConfig {
+    fn default() -> Self {
+        SyncConfig {
+            role: SyncRole::Off,
+            leader: "127.0.0.1".to_string(),
+            clock_port: 8766,
+        }
+    }
+}
+
+/// Output of a projector started with `--headless`, which renders without a window
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct HeadlessConfig {
+    pub width: u32,
+    pub height: u32,
+    /// Directory every `dump_every`th frame is written to as PNG
+    pub dump: Option<String>,
+    pub dump_every: u32,
+}
+
+impl Default for HeadlessConfig {
+    fn default() -> Self {
+        HeadlessConfig {
+            width: 1280,
+            height: 720,
+            dump: None,
+            dump_every: 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SubtitlePosition {
+    Bottom,
+    Top,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SubtitleStyle {
+    /// TTF/OTF file, raylib's built-in font is used when unset
+    pub font: Option<String>,
+    /// Text height as a fraction of the output height
+    pub size: f32,
+    /// Outline thickness in pixels, 0 disables it
+    pub outline: f32,
+    pub color: [u8; 4],
+    pub outline_color: [u8; 4],
+    /// Margin from the screen edge as a fraction of the output height
+    pub safe_area: f32,
+    pub position: SubtitlePosition,
+}
+
+impl Default for SubtitleStyle {
+    fn default() -> Self {
+        SubtitleStyle {
+            font: None,
+            size: 0.05,
+            outline: 2.0,
+            color: [255, 255, 255, 255],
+            outline_color: [0, 0, 0, 255],
+            safe_area: 0.05,
+            position: SubtitlePosition::Bottom,
+        }
+    }
+}
@@ -0,0 +1,355 @@
+use anyhow::{anyhow, Error};
+use gstreamer as gst;
+use gstreamer_video::{VideoColorMatrix, VideoColorRange, VideoFormat, VideoFrameRef, VideoInfo};
+use crate::layout::Placement;
+use raylib::prelude::*;
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, AtomicU64, Ordering};
+use std::thread::{self, Thread};
+use std::time::{Duration, Instant};
+
+/// Formats the appsink accepts, decoders usually output the YUV ones directly
+pub const YUV_FORMATS: [&str; 2] = ["I420", "NV12"];
+pub const RGBA_FORMAT: &str = "RGBA";
+
+const YUV_SHADER: &str = r#"
+#version 330
+in vec2 fragTexCoord;
+in vec4 fragColor;
+out vec4 finalColor;
+
+uniform sampler2D texture0;
+uniform sampler2D texture1;
+uniform sampler2D texture2;
+uniform int semiPlanar;
+uniform int fullRange;
+uniform float kr;
+uniform float kb;
+
+void main() {
+    float y = texture(texture0, fragTexCoord).r;
+    vec2 uv;
+    if (semiPlanar == 1) {
+        vec4 c = texture(texture1, fragTexCoord);
+        uv = vec2(c.r, c.a);
+    } else {
+        uv = vec2(texture(texture1, fragTexCoord).r, texture(texture2, fragTexCoord).r);
+    }
+
+    if (fullRange == 1) {
+        uv -= 0.5;
+    } else {
+        y = (y - 16.0 / 255.0) * 255.0 / 219.0;
+        uv = (uv - 128.0 / 255.0) * 255.0 / 224.0;
+    }
+
+    float r = y + 2.0 * (1.0 - kr) * uv.y;
+    float b = y + 2.0 * (1.0 - kb) * uv.x;
+    float g = (y - kr * r - kb * b) / (1.0 - kr - kb);
+    finalColor = vec4(clamp(vec3(r, g, b), 0.0, 1.0), 1.0) * fragColor;
+}
+"#;
+
+/// Lock-free hand-off of the newest decoded sample to the render thread. Together with
+/// the sample being decoded and the one on screen this triple-buffers the video, and
+/// the frame data itself is never copied on the way
+pub struct FrameSlot {
+    latest: AtomicPtr<gst::Sample>,
+    dropped: AtomicU64,
+    // woken up on every new frame, see `FramePacing::Content`
+    render_thread: Thread,
+}
+
+impl FrameSlot {
+    /// Must be created on the render thread
+    pub fn new() -> Self {
+        FrameSlot {
+            latest: AtomicPtr::new(ptr::null_mut()),
+            dropped: AtomicU64::new(0),
+            render_thread: thread::current(),
+        }
+    }
+
+    /// Replaces the pending sample, a frame that was never shown counts as dropped
+    pub fn publish(&self, sample: gst::Sample) {
+        let new = Box::into_raw(Box::new(sample));
+        let old = self.latest.swap(new, Ordering::AcqRel);
+        if !old.is_null() {
+            // SAFETY: every non-null pointer in the slot comes from `Box::into_raw` and
+            // the swap gave us sole ownership of it
+            drop(unsafe { Box::from_raw(old) });
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        self.render_thread.unpark();
+    }
+
+    pub fn take(&self) -> Option<gst::Sample> {
+        let ptr = self.latest.swap(ptr::null_mut(), Ordering::AcqRel);
+        // SAFETY: see `publish`
+        (!ptr.is_null()).then(|| *unsafe { Box::from_raw(ptr) })
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for FrameSlot {
+    fn drop(&mut self) {
+        self.take();
+    }
+}
+
+enum FrameTextures {
+    Rgba(Texture2D),
+    /// I420 uses all three planes, NV12 keeps U and V interleaved in `u`
+    Yuv {
+        y: Texture2D,
+        u: Texture2D,
+        v: Option<Texture2D>,
+    },
+}
+
+/// Uploads samples into GPU textures, converting YUV in a shader
+pub struct FrameRenderer {
+    textures: Option<FrameTextures>,
+    format: VideoFormat,
+    width: u32,
+    height: u32,
+    // what the YUV uniforms were last set for
+    colorimetry: Option<(VideoColorMatrix, VideoColorRange)>,
+    shader: Shader,
+    texture1_loc: i32,
+    texture2_loc: i32,
+    // planes with padded rows are packed here before uploading
+    scratch: Vec<u8>,
+    pub upload_time: Duration,
+    pub average_upload_time: Duration,
+}
+
+impl FrameRenderer {
+    pub fn new(rl: &mut RaylibHandle, thread: &RaylibThread) -> Self {
+        let shader = rl.load_shader_from_memory(thread, None, Some(YUV_SHADER));
+        let texture1_loc = shader.get_shader_location("texture1");
+        let texture2_loc = shader.get_shader_location("texture2");
+
+        FrameRenderer {
+            textures: None,
+            format: VideoFormat::Unknown,
+            width: 0,
+            height: 0,
+            colorimetry: None,
+            shader,
+            texture1_loc,
+            texture2_loc,
+            scratch: Vec::new(),
+            upload_time: Duration::ZERO,
+            average_upload_time: Duration::ZERO,
+        }
+    }
+
+    /// Size of the last uploaded frame
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    pub fn upload(
+        &mut self,
+        rl: &mut RaylibHandle,
+        thread: &RaylibThread,
+        sample: &gst::Sample,
+    ) -> Result<(), Error> {
+        let started = Instant::now();
+
+        let caps = sample.caps().ok_or_else(|| anyhow!("Sample without caps"))?;
+        let info = VideoInfo::from_caps(caps)?;
+        let buffer = sample.buffer().ok_or_else(|| anyhow!("Sample without buffer"))?;
+        let frame = VideoFrameRef::from_buffer_ref_readable(buffer, &info)?;
+
+        // every sample carries its caps, so a mid-stream resolution or format change
+        // just reallocates the textures here on the render thread
+        if self.textures.is_none()
+            || info.format() != self.format
+            || info.width() != self.width
+            || info.height() != self.height
+        {
+            self.create_textures(rl, thread, &info)?;
+        }
+        // the colorimetry may change mid-stream without the size or format changing
+        if let Some(FrameTextures::Yuv { v, .. }) = &self.textures {
+            let colorimetry = (info.colorimetry().matrix(), info.colorimetry().range());
+            if self.colorimetry != Some(colorimetry) {
+                let semi_planar = v.is_none();
+                self.set_yuv_uniforms(&info, semi_planar);
+            }
+        }
+
+        let (width, height) = (info.width() as usize, info.height() as usize);
+        let (chroma_width, chroma_height) = (width.div_ceil(2), height.div_ceil(2));
+        let scratch = &mut self.scratch;
+        match &mut self.textures {
+            Some(FrameTextures::Rgba(texture)) => {
+                upload_plane(texture, &frame, 0, width * 4, height, scratch)?;
+            }
+            Some(FrameTextures::Yuv { y, u, v }) => {
+                upload_plane(y, &frame, 0, width, height, scratch)?;
+                match v {
+                    Some(v) => {
+                        upload_plane(u, &frame, 1, chroma_width, chroma_height, scratch)?;
+                        upload_plane(v, &frame, 2, chroma_width, chroma_height, scratch)?;
+                    }
+                    None => upload_plane(u, &frame, 1, chroma_width * 2, chroma_height, scratch)?,
+                }
+            }
+            None => {}
+        }
+
+        self.upload_time = started.elapsed();
+        // exponential moving average over roughly the last 30 frames
+        self.average_upload_time = (self.average_upload_time * 29 + self.upload_time) / 30;
+        Ok(())
+    }
+
+    fn create_textures(
+        &mut self,
+        rl: &mut RaylibHandle,
+        thread: &RaylibThread,
+        info: &VideoInfo,
+    ) -> Result<(), Error> {
+        let (width, height) = (info.width() as i32, info.height() as i32);
+        let (chroma_width, chroma_height) = ((width + 1) / 2, (height + 1) / 2);
+
+        let gray = PixelFormat::PIXELFORMAT_UNCOMPRESSED_GRAYSCALE;
+        let mut texture = |w, h, format| make_texture(rl, thread, w, h, format);
+
+        let textures = match info.format() {
+            VideoFormat::Rgba => FrameTextures::Rgba(texture(
+                width,
+                height,
+                PixelFormat::PIXELFORMAT_UNCOMPRESSED_R8G8B8A8,
+            )?),
+            VideoFormat::I420 => FrameTextures::Yuv {
+                y: texture(width, height, gray)?,
+                u: texture(chroma_width, chroma_height, gray)?,
+                v: Some(texture(chroma_width, chroma_height, gray)?),
+            },
+            VideoFormat::Nv12 => FrameTextures::Yuv {
+                y: texture(width, height, gray)?,
+                u: texture(
+                    chroma_width,
+                    chroma_height,
+                    PixelFormat::PIXELFORMAT_UNCOMPRESSED_GRAY_ALPHA,
+                )?,
+                v: None,
+            },
+            format => return Err(anyhow!("Unsupported video format {:?}", format)),
+        };
+
+        self.textures = Some(textures);
+        // the new textures may be planar where the old ones weren't
+        self.colorimetry = None;
+        self.format = info.format();
+        self.width = info.width();
+        self.height = info.height();
+        Ok(())
+    }
+
+    fn set_yuv_uniforms(&mut self, info: &VideoInfo, semi_planar: bool) {
+        let colorimetry = info.colorimetry();
+        let (kr, kb) = match colorimetry.matrix() {
+            VideoColorMatrix::Bt601 => (0.299f32, 0.114f32),
+            VideoColorMatrix::Bt2020 => (0.2627, 0.0593),
+            _ => (0.2126, 0.0722),
+        };
+        let full_range = colorimetry.range() == VideoColorRange::Range0255;
+
+        for (name, value) in [("semiPlanar", semi_planar as i32), ("fullRange", full_range as i32)] {
+            let loc = self.shader.get_shader_location(name);
+            self.shader.set_shader_value(loc, value);
+        }
+        for (name, value) in [("kr", kr), ("kb", kb)] {
+            let loc = self.shader.get_shader_location(name);
+            self.shader.set_shader_value(loc, value);
+        }
+        self.colorimetry = Some((colorimetry.matrix(), colorimetry.range()));
+    }
+
+    /// Works on any draw target, e.g. a render texture for the blurred background
+    /// `crop` is the part of the frame to draw, as fractions of its size
+    pub fn draw<D: RaylibDraw>(&self, d: &mut D, placement: &Placement, crop: &Rectangle) {
+        let (width, height) = (self.width as f32, self.height as f32);
+        let source = Rectangle::new(
+            crop.x * width,
+            crop.y * height,
+            crop.width * width,
+            crop.height * height,
+        );
+        let dest = Rectangle::new(
+            placement.center.x,
+            placement.center.y,
+            placement.size.x,
+            placement.size.y,
+        );
+        let origin = placement.size / 2.0;
+        let rotation = placement.rotation;
+        match &self.textures {
+            Some(FrameTextures::Rgba(texture)) => {
+                d.draw_texture_pro(texture, source, dest, origin, rotation, Color::WHITE);
+            }
+            Some(FrameTextures::Yuv { y, u, v }) => {
+                let shader = *self.shader.as_ref();
+                // samplers have to be bound inside shader mode, raylib resets them per batch
+                // SAFETY: plain raylib calls on a shader and textures we own, shader mode
+                // is ended before returning
+                unsafe {
+                    raylib::ffi::BeginShaderMode(shader);
+                    raylib::ffi::SetShaderValueTexture(shader, self.texture1_loc, *u.as_ref());
+                    if let Some(v) = v {
+                        raylib::ffi::SetShaderValueTexture(shader, self.texture2_loc, *v.as_ref());
+                    }
+                }
+                d.draw_texture_pro(y, source, dest, origin, rotation, Color::WHITE);
+                unsafe { raylib::ffi::EndShaderMode() };
+            }
+            None => {}
+        }
+    }
+}
+
+pub(crate) fn make_texture(
+    rl: &mut RaylibHandle,
+    thread: &RaylibThread,
+    width: i32,
+    height: i32,
+    format: PixelFormat,
+) -> Result<Texture2D, Error> {
+    let mut img = Image::gen_image_color(width, height, Color::BLACK);
+    img.set_format(format);
+    Ok(rl.load_texture_from_image(thread, &img)?)
+}
+
+/// Uploads one plane straight from the mapped buffer, rows padded for alignment are
+/// packed first since raylib expects tightly packed data
+fn upload_plane(
+    texture: &mut Texture2D,
+    frame: &VideoFrameRef<&gst::BufferRef>,
+    plane: u32,
+    row_bytes: usize,
+    rows: usize,
+    scratch: &mut Vec<u8>,
+) -> Result<(), Error> {
+    let data = frame.plane_data(plane)?;
+    let stride = frame.plane_stride()[plane as usize] as usize;
+
+    if stride == row_bytes {
+        texture.update_texture(&data[..row_bytes * rows])?;
+    } else {
+        scratch.clear();
+        for row in data.chunks(stride).take(rows) {
+            scratch.extend_from_slice(&row[..row_bytes]);
+        }
+        texture.update_texture(scratch)?;
+    }
+
+    Ok(())
+}
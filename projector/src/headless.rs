@@ -0,0 +1,401 @@
+use crate::backend::{BackendEvent, Frame, RenderBackend, Scene};
+use crate::blend::edge_mask;
+use crate::config::{FramePacing, HeadlessConfig, ProjectorConfig, WindowConfig};
+use crate::layout::{place, Placement, Viewport};
+use crate::software::{
+    blur, clear, draw_circle_lines, draw_line, draw_mapped, multiply, remap, PixelMap, SoftFont,
+};
+use crate::subtitles::{layout_subtitles, OUTLINE_OFFSETS};
+use crate::video::RaylibVideo;
+use crate::warp::test_grid;
+use log::warn;
+use raylib::prelude::{Color, Rectangle, Vector2};
+use shared::{Background, ImageFormat, Monitor, ScaleMode};
+use std::path::PathBuf;
+use std::sync::mpsc::{sync_channel, SyncSender, TrySendError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+// the window backend's blur renders this many times smaller and tints the result
+const BLUR_DOWNSCALE: u32 = 8;
+const BLUR_TINT: Color = Color::new(110, 110, 110, 255);
+// frames waiting to be written, more are dropped rather than stalling the render loop
+const DUMP_QUEUE: usize = 2;
+
+/// Renders into memory without a window, for machines without a display such as CI.
+/// Composes the same scene as the window backend in software. Text needs
+/// `[subtitles] font`, as raylib's built-in font only exists with a window
+pub struct HeadlessBackend {
+    output: Frame,
+    /// Newest decoded frame of the current item
+    video_frame: Option<Frame>,
+    video_map: PixelMap,
+    blur_map: PixelMap,
+    background_map: PixelMap,
+    warp_map: PixelMap,
+    /// `None` frame when the image failed to load, so it isn't retried every frame
+    background_image: Option<(String, Option<Frame>)>,
+    edge_mask: Option<((u32, u32), Vec<u8>)>,
+    font: Option<SoftFont>,
+    dump: Option<(PathBuf, SyncSender<(PathBuf, Frame)>)>,
+    dump_worker: Option<JoinHandle<()>>,
+    dump_every: u64,
+    presented: u64,
+    // frames presented in the current second, for the statistics overlay
+    fps: (Instant, u32, u32),
+    // fixed pacing has no vsync or raylib to wait for
+    frame_interval: Option<Duration>,
+    next_frame: Instant,
+}
+
+impl HeadlessBackend {
+    pub fn new(config: &ProjectorConfig) -> Self {
+        let HeadlessConfig {
+            width,
+            height,
+            dump,
+            dump_every,
+        } = &config.headless;
+
+        let dump = dump.as_ref().map(PathBuf::from);
+        if let Some(dir) = &dump {
+            if let Err(err) = std::fs::create_dir_all(dir) {
+                eprintln!("Failed to create frame dump directory {:?}: {}", dir, err);
+            }
+        }
+        // PNG encoding takes longer than a frame, so it runs on its own thread
+        let (dump, dump_worker) = match dump {
+            Some(dir) => {
+                let (tx, rx) = sync_channel::<(PathBuf, Frame)>(DUMP_QUEUE);
+                let worker = std::thread::spawn(move || {
+                    for (path, frame) in rx {
+                        let result = frame
+                            .encode(ImageFormat::Png, (frame.width, frame.height))
+                            .and_then(|png| Ok(std::fs::write(&path, png)?));
+                        if let Err(err) = result {
+                            eprintln!("Failed to dump frame {:?}: {}", path, err);
+                        }
+                    }
+                });
+                (Some((dir, tx)), Some(worker))
+            }
+            None => (None, None),
+        };
+
+        let font = match &config.subtitles.font {
+            Some(path) => SoftFont::load(path)
+                .map_err(|e| eprintln!("Failed to load subtitle font: {}", e))
+                .ok(),
+            None => {
+                eprintln!("Rendering headless without text, it needs [subtitles] font");
+                None
+            }
+        };
+        let frame_interval = (config.video.pacing == FramePacing::Fixed).then(|| {
+            Duration::from_secs_f64(1.0 / config.video.target_fps.max(1) as f64)
+        });
+        println!("Rendering headless at {}x{}", width, height);
+
+        HeadlessBackend {
+            output: Frame::black((*width).max(1), (*height).max(1)),
+            video_frame: None,
+            video_map: PixelMap::new(),
+            blur_map: PixelMap::new(),
+            background_map: PixelMap::new(),
+            warp_map: PixelMap::new(),
+            background_image: None,
+            edge_mask: None,
+            font,
+            dump,
+            dump_worker,
+            dump_every: (*dump_every).max(1) as u64,
+            presented: 0,
+            fps: (Instant::now(), 0, 0),
+            frame_interval,
+            next_frame: Instant::now(),
+        }
+    }
+
+    fn dump_frame(&self) {
+        let Some((dir, tx)) = &self.dump else {
+            return;
+        };
+        if self.presented % self.dump_every != 0 {
+            return;
+        }
+
+        let path = dir.join(format!("frame-{:06}.png", self.presented));
+        if let Err(TrySendError::Full((path, _))) = tx.try_send((path, self.output.clone())) {
+            eprintln!("Frame dumps fall behind, skipped {:?}", path);
+        }
+    }
+
+    /// The blurred copy of the video or the image behind it, like `BackgroundRenderer`
+    fn draw_background(&mut self, v: &RaylibVideo, frame: &Frame, viewport: &Viewport) {
+        let output = (self.output.width, self.output.height);
+        let (canvas_width, canvas_height) = viewport.canvas;
+        // stretched over the whole canvas
+        let canvas = Placement {
+            center: Vector2::new(canvas_width / 2.0, canvas_height / 2.0),
+            size: Vector2::new(canvas_width, canvas_height),
+            rotation: 0.0,
+        };
+        let whole = Rectangle::new(0.0, 0.0, 1.0, 1.0);
+
+        match &v.background {
+            Background::Black => {}
+            Background::Blur => {
+                let (pixels_width, pixels_height) = viewport.canvas_pixels();
+                let small = (
+                    (pixels_width as u32 / BLUR_DOWNSCALE).max(1),
+                    (pixels_height as u32 / BLUR_DOWNSCALE).max(1),
+                );
+                let small_size = (small.0 as f32, small.1 as f32);
+                let fill = place(ScaleMode::Fill, v.rotation, v.display_size(), small_size);
+                let table = self.blur_map.placed(
+                    small,
+                    (frame.width, frame.height),
+                    &fill,
+                    &v.crop,
+                    &Viewport::full(small_size),
+                );
+                let mut filled = Frame::black(small.0, small.1);
+                draw_mapped(&mut filled, frame, table);
+                let blurred = blur(&filled, BLUR_TINT);
+
+                let table = self.background_map.placed(
+                    output,
+                    small,
+                    &canvas,
+                    &whole,
+                    viewport,
+                );
+                draw_mapped(&mut self.output, &blurred, table);
+            }
+            Background::Image(path) => {
+                if !self.background_image.as_ref().is_some_and(|(loaded, _)| loaded == path) {
+                    let image = Frame::load(path)
+                        .map_err(|e| eprintln!("Failed to load background image {:?}: {}", path, e))
+                        .ok();
+                    self.background_image = Some((path.clone(), image));
+                }
+                let Some((_, Some(image))) = &self.background_image else {
+                    return;
+                };
+
+                let size = (image.width as f32, image.height as f32);
+                let fill = place(ScaleMode::Fill, 0, size, viewport.canvas);
+                let table = self.background_map.placed(
+                    output,
+                    (image.width, image.height),
+                    &fill,
+                    &whole,
+                    viewport,
+                );
+                draw_mapped(&mut self.output, image, table);
+            }
+        }
+    }
+
+    /// Subtitles laid out on the canvas like the window backend, then drawn through the
+    /// viewport
+    fn draw_subtitles(&mut self, config: &ProjectorConfig, text: &str, viewport: &Viewport) {
+        let Some(font) = &self.font else {
+            return;
+        };
+        let style = &config.subtitles;
+        let (canvas_width, canvas_height) = viewport.canvas;
+        let layout = layout_subtitles(style, text, canvas_width, canvas_height, |line, size, spacing| {
+            font.measure(line, size, spacing)
+        });
+
+        let scale = viewport.scale;
+        let to_output = |p: Vector2| {
+            Vector2::new(
+                (p.x - viewport.origin.0) * scale.0,
+                (p.y - viewport.origin.1) * scale.1,
+            )
+        };
+        let (size, spacing) = (layout.font_size * scale.1, layout.spacing * scale.0);
+        for (line, position) in layout.lines {
+            if style.outline > 0.0 {
+                for (ox, oy) in OUTLINE_OFFSETS {
+                    let offset = Vector2::new(ox * style.outline, oy * style.outline);
+                    let position = to_output(position + offset);
+                    font.draw(&mut self.output, line, position, size, spacing, layout.outline_color);
+                }
+            }
+            font.draw(&mut self.output, line, to_output(position), size, spacing, layout.color);
+        }
+    }
+
+    /// Multi-line text with raylib's line spacing, like `draw_text`
+    fn draw_text(&mut self, text: &str, x: f32, y: f32, size: f32, color: Color) {
+        let Some(font) = &self.font else {
+            return;
+        };
+        for (i, line) in text.lines().enumerate() {
+            let position = Vector2::new(x, y + (size + 2.0) * i as f32);
+            font.draw(&mut self.output, line, position, size, size / 10.0, color);
+        }
+    }
+
+    /// Frames presented in the last full second, like raylib's `draw_fps`
+    fn draw_fps(&mut self) {
+        let fps = self.fps.2;
+        self.draw_text(&format!("{} FPS", fps), 0.0, 0.0, 20.0, Color::LIME);
+    }
+}
+
+impl Drop for HeadlessBackend {
+    fn drop(&mut self) {
+        // the worker writes what is queued and stops once the channel is closed
+        self.dump = None;
+        if let Some(worker) = self.dump_worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl RenderBackend for HeadlessBackend {
+    fn should_close(&self) -> bool {
+        false
+    }
+
+    fn events(&mut self) -> Vec<BackendEvent> {
+        Vec::new()
+    }
+
+    fn screen_size(&self) -> (i32, i32) {
+        (self.output.width as i32, self.output.height as i32)
+    }
+
+    fn monitors(&self) -> Vec<Monitor> {
+        vec![Monitor {
+            index: 0,
+            name: "headless".to_string(),
+            width: self.output.width as i32,
+            height: self.output.height as i32,
+            x: 0,
+            y: 0,
+        }]
+    }
+
+    fn place_window(&mut self, _window: &WindowConfig) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn match_refresh_rate(&mut self, _fps: f64) {}
+
+    fn snapshot(&mut self) -> Option<Frame> {
+        Some(self.output.clone())
+    }
+
+    fn present(&mut self, scene: Scene<'_>) {
+        let Scene {
+            video,
+            config,
+            show_subtitles,
+            show_stats,
+            show_test_grid,
+            addresses,
+            capture: _,
+        } = scene;
+        let (width, height) = (self.output.width, self.output.height);
+        let screen = (width as f32, height as f32);
+        let viewport = config.wall.viewport(screen);
+
+        clear(&mut self.output);
+        if let Some(v) = video {
+            let placement = v.placement(viewport.canvas.0, viewport.canvas.1);
+            v.set_output_size(
+                (placement.size.x * viewport.scale.0).ceil() as u32,
+                (placement.size.y * viewport.scale.1).ceil() as u32,
+            );
+            if let Some(sample) = v.take_frame() {
+                match Frame::from_sample(&sample) {
+                    Ok(frame) => self.video_frame = Some(frame),
+                    Err(err) => warn!("Failed to read video frame: {}", err),
+                }
+            }
+
+            if let Some(frame) = self.video_frame.take() {
+                if v.has_frame() {
+                    self.draw_background(v, &frame, &viewport);
+                    let table = self.video_map.placed(
+                        (width, height),
+                        (frame.width, frame.height),
+                        &placement,
+                        &v.crop,
+                        &viewport,
+                    );
+                    draw_mapped(&mut self.output, &frame, table);
+                }
+                self.video_frame = Some(frame);
+            }
+
+            if show_subtitles {
+                if let Some(text) = v.subtitle_text() {
+                    self.draw_subtitles(config, &text, &viewport);
+                }
+            }
+            if show_stats {
+                self.draw_fps();
+                if let Some(stats) = v.frame_stats() {
+                    self.draw_text(&stats, 12.0, 24.0, 18.0, Color::WHITE);
+                }
+            }
+        } else {
+            self.video_frame = None;
+            if !addresses.is_empty() {
+                self.draw_fps();
+                let title = format!("SimplePresenter Projector\n\nScreen: {width}X{height}");
+                self.draw_text(&title, 12.0, 12.0, 18.0, Color::WHITE);
+
+                let x = screen.0 / 2.0 - 240.0;
+                let mut y = screen.1 / 2.0 - 240.0;
+                self.draw_text("Server ready:", x, y, 18.0, Color::WHITE);
+                y += 24.0;
+                for address in addresses {
+                    self.draw_text(address, x + 20.0, y, 24.0, Color::WHITE);
+                    y += 24.0;
+                }
+            }
+        }
+
+        if config.blend.has_edges() {
+            if !self.edge_mask.as_ref().is_some_and(|(size, _)| *size == (width, height)) {
+                let mask = edge_mask(&config.blend, (width as i32, height as i32));
+                self.edge_mask = Some(((width, height), mask));
+            }
+            if let Some((_, mask)) = &self.edge_mask {
+                multiply(&mut self.output, mask);
+            }
+        } else {
+            self.edge_mask = None;
+        }
+        if show_test_grid {
+            let (lines, (center, radius)) = test_grid(screen.0, screen.1);
+            for (start, end, color) in lines {
+                draw_line(&mut self.output, start, end, color);
+            }
+            draw_circle_lines(&mut self.output, center, radius, Color::RED);
+        }
+        if !config.warp.is_identity() {
+            let table = self.warp_map.warped((width, height), &config.warp);
+            self.output = remap(&self.output, table);
+        }
+
+        self.dump_frame();
+        self.presented += 1;
+        self.fps.1 += 1;
+        if self.fps.0.elapsed() >= Duration::from_secs(1) {
+            self.fps = (Instant::now(), 0, self.fps.1);
+        }
+
+        if let Some(interval) = self.frame_interval {
+            std::thread::sleep(self.next_frame.saturating_duration_since(Instant::now()));
+            self.next_frame = Instant::now().max(self.next_frame + interval);
+        }
+    }
+}
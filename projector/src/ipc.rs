@@ -1,22 +1,25 @@
+use crate::output::is_extra_output;
 use futures_util::{SinkExt, StreamExt};
 use shared::ProjectorCommand;
 use tokio::sync::mpsc;
 use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
 use tokio_tungstenite::tungstenite::Message;
 
-pub fn start_ipc_server() -> (
+pub fn start_ipc_server(address: &str) -> (
     UnboundedSender<ProjectorCommand>,
     UnboundedReceiver<ProjectorCommand>,
 ) {
-    let (tx, mut rx) = mpsc::unbounded_channel();
+    let (tx, rx) = mpsc::unbounded_channel();
     let (write_tx, mut write_rx) = mpsc::unbounded_channel::<ProjectorCommand>();
+    let address = address.to_string();
+    if is_extra_output() {
+        read_parent_commands(tx.clone());
+    }
 
     std::thread::spawn(move || {
         let rt = tokio::runtime::Runtime::new().unwrap();
         rt.block_on(async move {
-            let listener = tokio::net::TcpListener::bind("127.0.0.1:8765")
-                .await
-                .unwrap();
+            let listener = tokio::net::TcpListener::bind(&address).await.unwrap();
             println!(
                 "Projector listening on ws://{}",
                 listener.local_addr().unwrap()
@@ -28,18 +31,31 @@ pub fn start_ipc_server() -> (
 
                 let (mut write, mut read) = ws_stream.split();
 
+                // replies and events have to go out while commands are still coming in
+                tokio::spawn(async move {
+                    while let Some(cmd) = write_rx.recv().await {
+                        let message = match cmd.to_binary() {
+                            Some(data) => Message::Binary(data.into()),
+                            None => Message::Text(cmd.to_message().into()),
+                        };
+                        if let Err(e) = write.send(message).await {
+                            eprintln!("Failed to send WS message: {}", e);
+                            break;
+                        }
+                    }
+                });
+
                 let tx_clone = tx.clone();
                 while let Some(msg) = read.next().await {
                     match msg {
                         Ok(Message::Text(text)) => {
                             println!("Got: {}", text);
 
-                            //parse
-                            let parts: Vec<_> = text.split("\n").collect();
-                            if parts.get(0) == Some(&"START") && parts.len() >= 3 {
-                                let path = parts[1].to_string();
-                                let skip = parts[2].to_string();
-                                let _ = tx_clone.send(ProjectorCommand::Start { path, skip });
+                            match ProjectorCommand::from_message(text.as_str()) {
+                                Some(cmd) => {
+                                    let _ = tx_clone.send(cmd);
+                                }
+                                None => eprintln!("Unknown command: {}", text),
                             }
                         }
                         Ok(_) => {}
@@ -50,21 +66,41 @@ pub fn start_ipc_server() -> (
                     }
                 }
 
-                while let Some(cmd) = write_rx.recv().await {
-                    let payload = match cmd {
-                        ProjectorCommand::Start { path, skip } => {
-                            format!("START\n{}\n{}", path, skip)
-                        }
-                        ProjectorCommand::VideoEnded => "VideoEnded".to_string(),
-                    };
-                    if let Err(e) = write.send(Message::Text(payload.into())).await {
-                        eprintln!("Failed to send WS message: {}", e);
-                        break;
-                    }
-                }
+                // nobody is left to watch the preview
+                println!("Presenter disconnected");
+                let _ = tx_clone.send(ProjectorCommand::StopPreview);
             }
         })
     });
 
     (write_tx, rx)
 }
+
+/// Commands the projector that started this one as an extra output passes on, which
+/// arrive on stdin with a blank line after each
+fn read_parent_commands(tx: UnboundedSender<ProjectorCommand>) {
+    std::thread::spawn(move || {
+        let mut message = Vec::new();
+        for line in std::io::stdin().lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if !line.is_empty() {
+                message.push(line);
+                continue;
+            }
+            if message.is_empty() {
+                continue;
+            }
+
+            let text = message.join("\n");
+            match ProjectorCommand::from_message(&text) {
+                Some(cmd) => {
+                    let _ = tx.send(cmd);
+                }
+                None => eprintln!("Unknown command from the first output: {}", text),
+            }
+            message.clear();
+        }
+    });
+}
@@ -0,0 +1,183 @@
+use raylib::ffi;
+use raylib::math::Vector2;
+use shared::ScaleMode;
+
+/// Where the frame is drawn: `size` is in the frame's own orientation and the frame is
+/// rotated about `center`
+#[derive(Debug, Clone, Copy)]
+pub struct Placement {
+    pub center: Vector2,
+    pub size: Vector2,
+    pub rotation: f32,
+}
+
+/// Rounds any angle to a clockwise quarter turn, 0, 90, 180 or 270
+pub fn quarter_turns(rotation: u32) -> u32 {
+    ((rotation % 360 + 45) / 90 % 4) * 90
+}
+
+/// Places a frame of `video` square pixels on a `screen` sized output
+pub fn place(mode: ScaleMode, rotation: u32, video: (f32, f32), screen: (f32, f32)) -> Placement {
+    let rotation = quarter_turns(rotation);
+    let (screen_width, screen_height) = screen;
+    // the size the frame takes up on screen once rotated
+    let (width, height) = if rotation % 180 == 0 {
+        video
+    } else {
+        (video.1, video.0)
+    };
+
+    let fit = (screen_width / width).min(screen_height / height);
+    let mut center = Vector2::new(screen_width / 2.0, screen_height / 2.0);
+    let (scale_x, scale_y) = match mode {
+        ScaleMode::Fit => (fit, fit),
+        ScaleMode::Fill => {
+            let fill = (screen_width / width).max(screen_height / height);
+            (fill, fill)
+        }
+        ScaleMode::Stretch => (screen_width / width, screen_height / height),
+        ScaleMode::Native => (1.0, 1.0),
+        ScaleMode::Custom { zoom, pan_x, pan_y } => {
+            center.x += pan_x * screen_width;
+            center.y += pan_y * screen_height;
+            (fit * zoom.max(0.01), fit * zoom.max(0.01))
+        }
+    };
+
+    let on_screen = Vector2::new(width * scale_x, height * scale_y);
+    let size = if rotation % 180 == 0 {
+        on_screen
+    } else {
+        Vector2::new(on_screen.y, on_screen.x)
+    };
+
+    Placement {
+        center,
+        size,
+        rotation: rotation as f32,
+    }
+}
+
+/// The part of a larger canvas shown on this output, for video walls. Everything is laid
+/// out on the canvas and drawn through the viewport
+#[derive(Debug, Clone, Copy)]
+pub struct Viewport {
+    /// Canvas size, in the wall's units
+    pub canvas: (f32, f32),
+    /// Top-left of the visible area on the canvas
+    pub origin: (f32, f32),
+    /// Output pixels per canvas unit
+    pub scale: (f32, f32),
+}
+
+impl Viewport {
+    /// The whole canvas is the output, one unit per pixel
+    pub fn full(screen: (f32, f32)) -> Self {
+        Viewport {
+            canvas: screen,
+            origin: (0.0, 0.0),
+            scale: (1.0, 1.0),
+        }
+    }
+
+    /// Stretches `visible`, `[x, y, width, height]` on the canvas, over the output
+    pub fn tile(canvas: (f32, f32), visible: [f32; 4], screen: (f32, f32)) -> Self {
+        let [x, y, width, height] = visible;
+        Viewport {
+            canvas,
+            origin: (x, y),
+            scale: (screen.0 / width.max(f32::EPSILON), screen.1 / height.max(f32::EPSILON)),
+        }
+    }
+
+    /// Canvas size in output pixels
+    pub fn canvas_pixels(&self) -> (f32, f32) {
+        (self.canvas.0 * self.scale.0, self.canvas.1 * self.scale.1)
+    }
+
+    /// Draws in canvas units until `end`
+    pub fn begin(&self) {
+        // SAFETY: plain rlgl matrix calls, popped again in `end`
+        unsafe {
+            ffi::rlPushMatrix();
+            ffi::rlScalef(self.scale.0, self.scale.1, 1.0);
+            ffi::rlTranslatef(-self.origin.0, -self.origin.1, 0.0);
+        }
+    }
+
+    pub fn end(&self) {
+        // SAFETY: pops the matrix pushed in `begin`
+        unsafe { ffi::rlPopMatrix() };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HD: (f32, f32) = (1920.0, 1080.0);
+
+    fn assert_placed(placement: Placement, center: (f32, f32), size: (f32, f32)) {
+        let near = |a: Vector2, b: (f32, f32)| (a.x - b.0).abs() < 0.01 && (a.y - b.1).abs() < 0.01;
+        assert!(near(placement.center, center), "center {:?} != {:?}", placement.center, center);
+        assert!(near(placement.size, size), "size {:?} != {:?}", placement.size, size);
+    }
+
+    #[test]
+    fn rotations_round_to_quarter_turns() {
+        assert_eq!(quarter_turns(0), 0);
+        assert_eq!(quarter_turns(44), 0);
+        assert_eq!(quarter_turns(45), 90);
+        assert_eq!(quarter_turns(180), 180);
+        assert_eq!(quarter_turns(300), 270);
+        assert_eq!(quarter_turns(350), 0);
+        assert_eq!(quarter_turns(450), 90);
+    }
+
+    #[test]
+    fn fit_letterboxes() {
+        let placement = place(ScaleMode::Fit, 0, (1000.0, 1000.0), HD);
+        assert_placed(placement, (960.0, 540.0), (1080.0, 1080.0));
+        assert_eq!(placement.rotation, 0.0);
+    }
+
+    #[test]
+    fn fill_covers() {
+        let placement = place(ScaleMode::Fill, 0, (1000.0, 1000.0), HD);
+        assert_placed(placement, (960.0, 540.0), (1920.0, 1920.0));
+    }
+
+    #[test]
+    fn stretch_ignores_the_aspect_ratio() {
+        let placement = place(ScaleMode::Stretch, 0, (1000.0, 1000.0), HD);
+        assert_placed(placement, (960.0, 540.0), (1920.0, 1080.0));
+    }
+
+    #[test]
+    fn native_keeps_the_pixels() {
+        let placement = place(ScaleMode::Native, 0, (640.0, 480.0), HD);
+        assert_placed(placement, (960.0, 540.0), (640.0, 480.0));
+    }
+
+    #[test]
+    fn custom_zooms_and_pans() {
+        let mode = ScaleMode::Custom {
+            zoom: 2.0,
+            pan_x: 0.25,
+            pan_y: -0.5,
+        };
+        let placement = place(mode, 0, (1000.0, 1000.0), HD);
+        assert_placed(placement, (1440.0, 0.0), (2160.0, 2160.0));
+    }
+
+    #[test]
+    fn quarter_turns_fit_the_rotated_frame() {
+        // a portrait phone video turned on its side fills a landscape output
+        let placement = place(ScaleMode::Fit, 90, (1080.0, 1920.0), HD);
+        assert_placed(placement, (960.0, 540.0), (1080.0, 1920.0));
+        assert_eq!(placement.rotation, 90.0);
+
+        let placement = place(ScaleMode::Stretch, 270, (1000.0, 500.0), HD);
+        assert_placed(placement, (960.0, 540.0), (1080.0, 1920.0));
+    }
+}
@@ -0,0 +1,410 @@
+use crate::probe::{audio_tracks, discover, ProbeCache};
+use crate::video::{build_discard_branch, link_branch};
+use anyhow::anyhow;
+use gstreamer as gst;
+use gstreamer::prelude::*;
+use gstreamer_app::AppSink;
+use log::warn;
+use shared::ProjectorCommand;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::UnboundedSender;
+
+// BS.1770 measures at 48 kHz, the filter coefficients below are for that rate
+const RATE: usize = 48000;
+const SUB_BLOCK: usize = RATE / 10;
+const SUB_BLOCKS_PER_BLOCK: usize = 4;
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+const RELATIVE_GATE_LU: f64 = -10.0;
+// decoding runs far faster than real time, anything slower is stuck
+const ANALYSIS_TIMEOUT: Duration = Duration::from_secs(600);
+// bits of GstAudioChannelPosition in the caps' channel-mask
+const LFE1: u32 = 3;
+const REAR_LEFT: u32 = 4;
+const REAR_RIGHT: u32 = 5;
+const LFE2: u32 = 9;
+const SIDE_LEFT: u32 = 10;
+const SIDE_RIGHT: u32 = 11;
+
+/// Measures an audio track in the background, stores the result in the cache and
+/// reports it with a `Loudness` event. Does nothing if the track is already being measured
+pub fn spawn_analysis(
+    cache: Arc<Mutex<ProbeCache>>,
+    cache_path: PathBuf,
+    path: String,
+    audio_track: u32,
+    tx: UnboundedSender<ProjectorCommand>,
+) {
+    match cache.lock() {
+        Ok(mut cache) if cache.begin_analysis(&path, audio_track) => {}
+        _ => return,
+    }
+
+    std::thread::spawn(move || {
+        let result = analyze(&path, audio_track);
+        let Ok(mut cache) = cache.lock() else {
+            return;
+        };
+        cache.end_analysis(&path, audio_track);
+
+        match result {
+            Ok(lufs) => {
+                cache.set_loudness(&path, audio_track, lufs);
+                if let Err(err) = cache.save(&cache_path) {
+                    warn!("{}", err);
+                }
+                let _ = tx.send(ProjectorCommand::Loudness {
+                    path,
+                    audio_track,
+                    lufs,
+                });
+            }
+            Err(err) => warn!("Failed to measure loudness of {:?}: {}", path, err),
+        }
+    });
+}
+
+/// Decodes an audio track of a file as fast as possible and measures its integrated
+/// loudness in LUFS. `audio_track` indexes the probed audio tracks
+pub fn analyze(path: &str, audio_track: u32) -> anyhow::Result<f64> {
+    // the stream ids derive from the uri, so use the one the discoverer and playback use
+    let (uri, info) = discover(path)?;
+    let stream_id = audio_tracks(&info)
+        .into_iter()
+        .nth(audio_track as usize)
+        .and_then(|track| track.stream_id)
+        .ok_or_else(|| anyhow!("Audio track {} does not exist", audio_track))?;
+
+    let pipeline = gst::Pipeline::new();
+    let decode = gst::ElementFactory::make("uridecodebin")
+        .property("uri", uri.as_str())
+        .build()?;
+    let convert = gst::ElementFactory::make("audioconvert").build()?;
+    let resample = gst::ElementFactory::make("audioresample").build()?;
+    let appsink = AppSink::builder()
+        .caps(
+            &gst::Caps::builder("audio/x-raw")
+                .field("format", "F32LE")
+                .field("layout", "interleaved")
+                .field("rate", RATE as i32)
+                .build(),
+        )
+        .sync(false)
+        .build();
+
+    let sink = appsink.upcast_ref::<gst::Element>();
+    pipeline.add_many([&decode, &convert, &resample, sink])?;
+    gst::Element::link_many([&convert, &resample, sink])?;
+
+    let audio_linked = Arc::new(AtomicBool::new(false));
+    let audio_linked_ref = audio_linked.clone();
+    let no_more_pads = Arc::new(AtomicBool::new(false));
+    let no_more_pads_ref = no_more_pads.clone();
+    decode.connect_no_more_pads(move |_| no_more_pads_ref.store(true, Ordering::SeqCst));
+    let weak_pipe = pipeline.downgrade();
+    let convert_sink = convert
+        .static_pad("sink")
+        .ok_or_else(|| anyhow!("audioconvert has no sink pad"))?;
+    decode.connect_pad_added(move |_, src_pad| {
+        let Some(pipeline) = weak_pipe.upgrade() else {
+            return;
+        };
+        let is_track = src_pad.stream_id().as_deref() == Some(stream_id.as_str());
+        let result = if is_track && !audio_linked_ref.swap(true, Ordering::SeqCst) {
+            src_pad
+                .link(&convert_sink)
+                .map(|_| ())
+                .map_err(anyhow::Error::from)
+                .inspect_err(|_| audio_linked_ref.store(false, Ordering::SeqCst))
+        } else {
+            build_discard_branch().and_then(|branch| link_branch(&pipeline, src_pad, &branch))
+        };
+
+        if let Err(err) = result {
+            warn!("Failed to link pad {} for loudness analysis: {}", src_pad.name(), err);
+        }
+    });
+
+    pipeline.set_state(gst::State::Playing)?;
+    // without an audio branch the appsink never sees EOS
+    let has_audio = || audio_linked.load(Ordering::SeqCst) || !no_more_pads.load(Ordering::SeqCst);
+    let result = measure(&pipeline, &appsink, has_audio);
+    let _ = pipeline.set_state(gst::State::Null);
+    result
+}
+
+/// Reads samples until EOS. `has_audio` turns false once it is clear no audio is coming
+fn measure(
+    pipeline: &gst::Pipeline,
+    appsink: &AppSink,
+    has_audio: impl Fn() -> bool,
+) -> anyhow::Result<f64> {
+    let bus = pipeline.bus().ok_or_else(|| anyhow!("Pipeline has no bus"))?;
+    let mut meter: Option<LoudnessMeter> = None;
+    let deadline = Instant::now() + ANALYSIS_TIMEOUT;
+
+    loop {
+        if Instant::now() >= deadline {
+            return Err(anyhow!("Loudness analysis timed out"));
+        }
+
+        if let Some(sample) = appsink.try_pull_sample(gst::ClockTime::from_seconds(1)) {
+            let structure = sample.caps().and_then(|caps| caps.structure(0));
+            let channels = structure
+                .and_then(|s| s.get::<i32>("channels").ok())
+                .unwrap_or(1) as usize;
+            // mono and stereo may leave the mask out
+            let channel_mask = structure
+                .and_then(|s| s.get::<gst::Bitmask>("channel-mask").ok())
+                .map_or(0, |mask| mask.0);
+            let buffer = sample
+                .buffer()
+                .ok_or_else(|| anyhow!("Sample without buffer"))?;
+            let map = buffer.map_readable()?;
+            let samples: Vec<f32> = map
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect();
+
+            meter
+                .get_or_insert_with(|| LoudnessMeter::new(channels, channel_mask))
+                .push(&samples);
+        } else if appsink.is_eos() {
+            break;
+        } else if !has_audio() {
+            return Err(anyhow!("No audio stream could be linked"));
+        } else if let Some(msg) = bus.pop_filtered(&[gst::MessageType::Error]) {
+            if let gst::MessageView::Error(err) = msg.view() {
+                return Err(anyhow!("Loudness analysis failed: {}", err.error()));
+            }
+        }
+    }
+
+    meter
+        .and_then(|m| m.integrated())
+        .ok_or_else(|| anyhow!("No audio to measure"))
+}
+
+/// Gain that brings `measured` LUFS to `target` LUFS, limited to `max_gain_db`
+pub fn normalization_gain(measured: f64, target: f64, max_gain_db: f64) -> f64 {
+    let gain_db = (target - measured).min(max_gain_db);
+    10f64.powf(gain_db / 20.0)
+}
+
+#[derive(Clone, Copy, Default)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    z: [f64; 2],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 2]) -> Self {
+        Biquad { b, a, z: [0.0; 2] }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.z[0];
+        self.z[0] = self.b[1] * x - self.a[0] * y + self.z[1];
+        self.z[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
+
+/// BS.1770 channel weights: the LFEs are ignored and the surrounds get +1.5 dB.
+/// Channels are interleaved in the order of their bits in GStreamer's `channel-mask`
+fn channel_weights(channels: usize, channel_mask: u64) -> Vec<f64> {
+    (0..64u32)
+        .filter(|bit| channel_mask & (1 << bit) != 0)
+        .map(|bit| match bit {
+            LFE1 | LFE2 => 0.0,
+            REAR_LEFT | REAR_RIGHT | SIDE_LEFT | SIDE_RIGHT => 1.41,
+            _ => 1.0,
+        })
+        .chain(std::iter::repeat(1.0))
+        .take(channels)
+        .collect()
+}
+
+/// K-weighted, gated loudness as specified by ITU-R BS.1770 / EBU R128
+struct LoudnessMeter {
+    channels: usize,
+    filters: Vec<[Biquad; 2]>,
+    weights: Vec<f64>,
+    sub_block_sums: Vec<f64>,
+    sub_block_len: usize,
+    /// Weighted power of the most recent 100 ms sub-blocks
+    recent: Vec<f64>,
+    block_powers: Vec<f64>,
+}
+
+impl LoudnessMeter {
+    fn new(channels: usize, channel_mask: u64) -> Self {
+        let shelf = Biquad::new(
+            [1.53512485958697, -2.69169618940638, 1.19839281085285],
+            [-1.69065929318241, 0.73248077421585],
+        );
+        let high_pass = Biquad::new([1.0, -2.0, 1.0], [-1.99004745483398, 0.99007225036621]);
+
+        LoudnessMeter {
+            channels,
+            filters: vec![[shelf, high_pass]; channels],
+            weights: channel_weights(channels, channel_mask),
+            sub_block_sums: vec![0.0; channels],
+            sub_block_len: 0,
+            recent: Vec::new(),
+            block_powers: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, interleaved: &[f32]) {
+        for frame in interleaved.chunks_exact(self.channels) {
+            for (c, sample) in frame.iter().enumerate() {
+                let [shelf, high_pass] = &mut self.filters[c];
+                let y = high_pass.process(shelf.process(*sample as f64));
+                self.sub_block_sums[c] += y * y;
+            }
+
+            self.sub_block_len += 1;
+            if self.sub_block_len == SUB_BLOCK {
+                self.finish_sub_block();
+            }
+        }
+    }
+
+    fn finish_sub_block(&mut self) {
+        let power: f64 = self
+            .sub_block_sums
+            .iter()
+            .zip(&self.weights)
+            .map(|(sum, weight)| weight * sum / SUB_BLOCK as f64)
+            .sum();
+
+        self.sub_block_sums.iter_mut().for_each(|s| *s = 0.0);
+        self.sub_block_len = 0;
+
+        // 400 ms blocks overlapping by 75%
+        self.recent.push(power);
+        if self.recent.len() > SUB_BLOCKS_PER_BLOCK {
+            self.recent.remove(0);
+        }
+        if self.recent.len() == SUB_BLOCKS_PER_BLOCK {
+            let block = self.recent.iter().sum::<f64>() / SUB_BLOCKS_PER_BLOCK as f64;
+            self.block_powers.push(block);
+        }
+    }
+
+    fn integrated(&self) -> Option<f64> {
+        let absolute: Vec<f64> = self
+            .block_powers
+            .iter()
+            .copied()
+            .filter(|p| power_to_lufs(*p) > ABSOLUTE_GATE_LUFS)
+            .collect();
+        if absolute.is_empty() {
+            return None;
+        }
+
+        let relative_gate = power_to_lufs(mean(&absolute)) + RELATIVE_GATE_LU;
+        let gated: Vec<f64> = absolute
+            .into_iter()
+            .filter(|p| power_to_lufs(*p) > relative_gate)
+            .collect();
+        if gated.is_empty() {
+            return None;
+        }
+
+        Some(power_to_lufs(mean(&gated)))
+    }
+}
+
+fn power_to_lufs(power: f64) -> f64 {
+    -0.691 + 10.0 * power.log10()
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(frequency: f64, amplitude: f64, seconds: f64, channels: usize) -> Vec<f32> {
+        let frames = (seconds * RATE as f64) as usize;
+        (0..frames)
+            .flat_map(|i| {
+                let t = i as f64 / RATE as f64;
+                let sample = (amplitude * (2.0 * std::f64::consts::PI * frequency * t).sin()) as f32;
+                std::iter::repeat_n(sample, channels)
+            })
+            .collect()
+    }
+
+    fn measure_samples(samples: &[f32], channels: usize, channel_mask: u64) -> Option<f64> {
+        let mut meter = LoudnessMeter::new(channels, channel_mask);
+        meter.push(samples);
+        meter.integrated()
+    }
+
+    #[test]
+    fn k_weighting_passes_dc_through_the_shelf_and_blocks_it_in_the_high_pass() {
+        let mut meter = LoudnessMeter::new(1, 0);
+        let [shelf, high_pass] = &mut meter.filters[0];
+        let (mut shelved, mut filtered) = (0.0, 0.0);
+        for _ in 0..RATE {
+            shelved = shelf.process(1.0);
+            filtered = high_pass.process(shelved);
+        }
+        assert!((shelved - 1.0).abs() < 1e-6, "shelf DC gain {}", shelved);
+        assert!(filtered.abs() < 1e-6, "high pass DC output {}", filtered);
+    }
+
+    #[test]
+    fn full_scale_sine_reads_minus_three() {
+        // BS.1770's reference: a 0 dBFS 997 Hz sine on one front channel is -3.01 LKFS
+        let lufs = measure_samples(&sine(997.0, 1.0, 5.0, 1), 1, 0).unwrap();
+        assert!((lufs + 3.01).abs() < 0.05, "{} LUFS", lufs);
+    }
+
+    #[test]
+    fn level_changes_show_one_to_one() {
+        let loud = measure_samples(&sine(997.0, 1.0, 5.0, 2), 2, 0b11).unwrap();
+        let quiet = measure_samples(&sine(997.0, 0.1, 5.0, 2), 2, 0b11).unwrap();
+        assert!((loud - quiet - 20.0).abs() < 0.05, "{} vs {}", loud, quiet);
+    }
+
+    #[test]
+    fn silence_is_gated() {
+        assert_eq!(measure_samples(&vec![0.0; RATE * 2], 1, 0), None);
+        assert_eq!(measure_samples(&sine(997.0, 1e-5, 2.0, 1), 1, 0), None);
+    }
+
+    #[test]
+    fn channel_weights_follow_the_mask() {
+        let stereo = 0b11;
+        let surround_5_0 = 0b11_0111;
+        let surround_5_1 = 0b11_1111;
+        let surround_7_1 = 0b1100_0011_1111;
+
+        assert_eq!(channel_weights(2, 0), vec![1.0, 1.0]);
+        assert_eq!(channel_weights(2, stereo), vec![1.0, 1.0]);
+        assert_eq!(channel_weights(5, surround_5_0), vec![1.0, 1.0, 1.0, 1.41, 1.41]);
+        assert_eq!(channel_weights(6, surround_5_1), vec![1.0, 1.0, 1.0, 0.0, 1.41, 1.41]);
+        assert_eq!(
+            channel_weights(8, surround_7_1),
+            vec![1.0, 1.0, 1.0, 0.0, 1.41, 1.41, 1.41, 1.41]
+        );
+        // unpositioned channels count fully
+        assert_eq!(channel_weights(4, 0), vec![1.0; 4]);
+    }
+
+    #[test]
+    fn normalization_gain_is_limited() {
+        assert!((normalization_gain(-29.0, -23.0, 12.0) - 1.9953).abs() < 1e-3);
+        assert!((normalization_gain(-17.0, -23.0, 12.0) - 0.5012).abs() < 1e-3);
+        assert!((normalization_gain(-50.0, -23.0, 6.0) - 1.9953).abs() < 1e-3);
+    }
+}
@@ -2,27 +2,69 @@
 #![windows_subsystem = "windows"]
 
 use std::env;
-use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 use anyhow::{anyhow, Error};
 use gstreamer_app::gst;
 use local_ip_address::list_afinet_netifas;
-use raylib::color::Color;
-use raylib::ffi::KeyboardKey;
-use raylib::math::Vector2;
-use raylib::prelude::RaylibDraw;
-use shared::ProjectorCommand;
+use shared::{ImageFormat, ProjectorCommand};
+use tokio::sync::mpsc::UnboundedSender;
+use crate::audio::{list_audio_devices, SilenceDetector};
+use crate::backend::{BackendEvent, RenderBackend, Scene};
+use crate::config::{FramePacing, ProjectorConfig, DEFAULT_CONFIG_PATH};
+use crate::headless::HeadlessBackend;
 use crate::ipc::start_ipc_server;
+use crate::loudness::{normalization_gain, spawn_analysis};
+use crate::output::Outputs;
+use crate::preview::PreviewStream;
+use crate::probe::{probe_tracks, ProbeCache};
+use crate::sync::SharedClock;
 use crate::video::RaylibVideo;
+use crate::window::WindowBackend;
 
 mod video;
+mod audio;
 mod ipc;
+mod probe;
+mod config;
+mod subtitles;
+mod loudness;
+mod frame;
+mod layout;
+mod background;
+mod warp;
+mod blend;
+mod sync;
+mod output;
+mod backend;
+mod window;
+mod capture;
+mod headless;
+mod software;
+mod preview;
 
 fn main() -> Result<(), Error> {
     gst::init()?;
 
+    // projector [CONFIG] [--headless]
+    let headless = env::args().skip(1).any(|arg| arg == "--headless");
+    let config_path = env::args()
+        .skip(1)
+        .find(|arg| !arg.starts_with("--"))
+        .unwrap_or_else(|| DEFAULT_CONFIG_PATH.to_string());
+    let mut config = ProjectorConfig::load(Path::new(&config_path))?;
+    if headless {
+        // frames are composed on the CPU, the YUV shader needs the window's GPU
+        config.video.yuv_upload = false;
+    }
+    let cache_path = Path::new(&config.probe_cache).to_path_buf();
+    let shared_clock = SharedClock::new(&config.sync);
+    let probe_cache = Arc::new(Mutex::new(ProbeCache::load(&cache_path)));
+
     // ipc
-    let (mut tx, mut rx) = start_ipc_server();
+    let (mut tx, mut rx) = start_ipc_server(&config.listen);
 
     /*let args: Vec<String> = env::args().collect();
     if args.len() != 2 {
@@ -30,100 +72,350 @@ fn main() -> Result<(), Error> {
     }
     let path = &args[1];*/
 
-    // raylib
-    let (mut rl, thread) = raylib::init()
-        .size(800, 600)
-        .resizable()
-        .title("SimplePresenter Projector")
-        .build();
+    let mut backend: Box<dyn RenderBackend> = if headless {
+        Box::new(HeadlessBackend::new(&config))
+    } else {
+        Box::new(WindowBackend::new(&config))
+    };
+    // the extra outputs live as long as this window and render the same way
+    let flags: Vec<String> = env::args().skip(1).filter(|arg| arg.starts_with("--")).collect();
+    let mut outputs = Outputs::spawn(&config.outputs, &flags);
 
-    rl.set_target_fps(60); // todo: configurable
+    // with content pacing the loop waits for frames itself, see below
+    let target_fps = config.video.target_fps.max(1);
+    let redraw_interval = Duration::from_secs_f64(1.0 / target_fps as f64);
+    let mut next_redraw = Instant::now();
+    let started = Instant::now();
 
     let mut video: Option<RaylibVideo> = None;
+    let mut show_test_grid = false;
+    // the warp is saved once dragging it around in the GUI has settled
+    let mut warp_changed_at: Option<Instant> = None;
 
-    let mut scale = 1.0;
-    let mut rotation = 0.0;
-    let mut pos = Vector2::new(0.0, 0.0);
 
-    let network_interfaces = list_afinet_netifas()?;
+    let addresses: Vec<String> = list_afinet_netifas()?
+        .iter()
+        .map(|(_, ip)| format!("ws://{ip:?}:{}", config.listen_port()))
+        .collect();
 
     let mut connected = false; // todo: do it correctly
 
-    while !rl.window_should_close() {
-        if let Some(key) = rl.get_key_pressed() {
-            match key {
-                KeyboardKey::KEY_F11 => {
-                    rl.toggle_fullscreen();
-                }
-                _ => {}
+    let mut show_subtitles = true;
+    let mut show_stats = false;
+
+    let mut master_volume = config.audio.master_volume;
+    let mut muted = false;
+    let mut reported_level = -1.0;
+    let mut reported_at = 0.0;
+    let mut silence = SilenceDetector::default();
+    // item still waiting for its loudness measurement
+    let mut pending_loudness: Option<(String, u32)> = None;
+    let mut playing_path: Option<String> = None;
+    // captured on the next frame, then encoded off the render loop
+    let mut pending_snapshot: Option<(u32, ImageFormat)> = None;
+    let mut preview = PreviewStream::new();
+
+    while !backend.should_close() {
+        if config.video.pacing == FramePacing::Content {
+            // the appsink wakes us up when a frame is due, otherwise redraw at target_fps
+            // for subtitles, fades and the idle screen
+            std::thread::park_timeout(next_redraw.saturating_duration_since(Instant::now()));
+            next_redraw = Instant::now() + redraw_interval;
+        }
+
+        for event in backend.events() {
+            match event {
+                BackendEvent::ToggleStats => show_stats = !show_stats,
             }
         }
 
         while let Ok(cmd) = rx.try_recv() {
             match cmd {
-                ProjectorCommand::Start { path, skip } => {
+                ProjectorCommand::Start { path, skip, options } => {
                     println!("Starting video, {:?} with skip {}", path, skip);
 
-                    if let Ok(mut v) = RaylibVideo::new(&path, &mut rl, &thread) {
+                    if let Ok(mut v) = {
+                        let (width, height) = backend.screen_size();
+                        RaylibVideo::new(&path, &options, &config, (width as u32, height as u32))
+                    } {
                         connected = true;
-                        v.play();
+                        playing_path = Some(path.clone());
+                        if config.video.match_refresh {
+                            if let Some(fps) = v.frame_rate() {
+                                backend.match_refresh_rate(fps);
+                            }
+                        }
+                        pending_loudness =
+                            normalize_loudness(&mut v, &path, &config, &probe_cache, &cache_path, &tx);
+                        if options.cue {
+                            v.cue(&shared_clock.clock);
+                        } else {
+                            v.play();
+                        }
                         video = Some(v);
                     }
                 }
+                ProjectorCommand::Probe { path } => {
+                    // the discoverer blocks for up to a few seconds, keep it off the render loop
+                    let tx = tx.clone();
+                    let probe_cache = probe_cache.clone();
+                    let cache_path = cache_path.clone();
+                    let normalize = config.loudness.normalize;
+                    std::thread::spawn(move || match probe_tracks(&path) {
+                        Ok((audio_tracks, subtitle_tracks)) => {
+                            let loudness = probe_cache.lock().ok().and_then(|c| c.loudness(&path, 0));
+                            if normalize && loudness.is_none() && !audio_tracks.is_empty() {
+                                spawn_analysis(probe_cache, cache_path, path.clone(), 0, tx.clone());
+                            }
+                            let _ = tx.send(ProjectorCommand::ProbeResult {
+                                path,
+                                audio_tracks,
+                                subtitle_tracks,
+                                loudness,
+                            });
+                        }
+                        Err(err) => eprintln!("Failed to probe {:?}: {}", path, err),
+                    });
+                }
+                ProjectorCommand::SelectAudioTrack(track) => {
+                    if let (Some(v), Some(path)) = (video.as_mut(), &playing_path) {
+                        v.select_audio_track(track);
+                        // tracks of one file can differ in loudness
+                        pending_loudness =
+                            normalize_loudness(v, path, &config, &probe_cache, &cache_path, &tx);
+                    }
+                }
+                ProjectorCommand::SelectSubtitleTrack(track) => {
+                    if let Some(ref mut v) = video {
+                        v.select_subtitle_track(track);
+                    }
+                }
+                ProjectorCommand::ShowSubtitles(visible) => {
+                    show_subtitles = visible;
+                }
+                ProjectorCommand::ListAudioDevices => {
+                    let tx = tx.clone();
+                    std::thread::spawn(move || {
+                        let _ = tx.send(ProjectorCommand::AudioDevices(list_audio_devices()));
+                    });
+                }
+                ProjectorCommand::SetMasterVolume(volume) => {
+                    master_volume = volume.max(0.0);
+                }
+                ProjectorCommand::SetMute(mute) => {
+                    muted = mute;
+                }
+                ProjectorCommand::SetScaleMode(mode) => {
+                    if let Some(ref mut v) = video {
+                        v.scale_mode = mode;
+                    }
+                }
+                ProjectorCommand::SetRotation(rotation) => {
+                    if let Some(ref mut v) = video {
+                        v.rotation = rotation;
+                    }
+                }
+                ProjectorCommand::SetWarp(new_warp) => {
+                    config.warp = new_warp;
+                    warp_changed_at = Some(Instant::now());
+                }
+                ProjectorCommand::GetWarp => {
+                    let _ = tx.send(ProjectorCommand::Warp(config.warp.clone()));
+                }
+                ProjectorCommand::ShowTestGrid(visible) => {
+                    show_test_grid = visible;
+                }
+                ProjectorCommand::GetClockTime => {
+                    if !shared_clock.is_synced() {
+                        eprintln!("Playback clock is not synchronised with the leader yet");
+                    }
+                    let _ = tx.send(ProjectorCommand::ClockTime(shared_clock.time()));
+                }
+                ProjectorCommand::StartAt { base_time } => {
+                    if let Some(ref mut v) = video {
+                        v.start_at(gst::ClockTime::from_nseconds(base_time));
+                    }
+                }
+                ProjectorCommand::ListMonitors => {
+                    let _ = tx.send(ProjectorCommand::Monitors(backend.monitors()));
+                }
+                ProjectorCommand::Snapshot { max_width, format } => {
+                    pending_snapshot = Some((max_width, format));
+                }
+                ProjectorCommand::StartPreview { max_width, fps } => {
+                    preview.start(max_width, fps);
+                }
+                ProjectorCommand::StopPreview => {
+                    preview.stop();
+                }
+                ProjectorCommand::MoveOutput {
+                    output: extra @ 1..,
+                    ..
+                } => {
+                    if let Err(err) = outputs.send(extra, &cmd) {
+                        eprintln!("Failed to move output {}: {}", extra, err);
+                    }
+                }
+                ProjectorCommand::MoveOutput {
+                    monitor,
+                    fullscreen,
+                    ..
+                } => {
+                    let mut window = config.window.clone();
+                    window.monitor = Some(monitor);
+                    window.fullscreen = fullscreen;
+                    match backend.place_window(&window) {
+                        Ok(()) => {
+                            config.window = window;
+                            if let Err(err) = config.save(Path::new(&config_path)) {
+                                eprintln!("Failed to save window placement: {}", err);
+                            }
+                        }
+                        Err(err) => eprintln!("Failed to move the output window: {}", err),
+                    }
+                }
                 _ => {}
             }
         }
 
 
-        let time = rl.get_time();
-        let mut d = rl.begin_drawing(&thread);
-        d.clear_background(Color::BLACK);
-
-        let screen_width = d.get_render_width() as f32;
-        let screen_height = d.get_render_height() as f32;
+        if warp_changed_at.is_some_and(|at| at.elapsed() >= Duration::from_secs(1)) {
+            warp_changed_at = None;
+            if let Err(err) = config.save(Path::new(&config_path)) {
+                eprintln!("Failed to save warp: {}", err);
+            }
+        }
 
-        if let Some(ref mut v) = video {
-            v.update();
+        let time = started.elapsed().as_secs_f64();
 
-            let scale_x = screen_width / v.width as f32;
-            let scale_y = screen_height / v.height as f32;
-            let scale = scale_x.min(scale_y);
+        if let (Some((path, audio_track)), Some(v)) = (&pending_loudness, video.as_mut()) {
+            if let Some(lufs) = probe_cache.lock().ok().and_then(|c| c.loudness(path, *audio_track)) {
+                v.set_normalization_gain(normalization_gain(
+                    lufs,
+                    config.loudness.target,
+                    config.loudness.max_gain_db,
+                ));
+                pending_loudness = None;
+            }
+        }
 
-            let draw_width = v.width as f32 * scale;
-            let draw_height = v.height as f32 * scale;
+        let level = match &video {
+            Some(v) => v.update_volume(master_volume, muted),
+            None => 0.0,
+        };
+        // fades change the level every frame, keep the GUI updates at a modest rate
+        if (level - reported_level).abs() > 0.005 && time - reported_at >= 0.1 {
+            let _ = tx.send(ProjectorCommand::VolumeLevel(level));
+            reported_level = level;
+            reported_at = time;
+        }
 
-            let pos_x = (screen_width - draw_width) as f64 * 0.5;
-            let pos_y = (screen_height - draw_height) as f64 * 0.5;
-            let pos = Vector2::new(pos_x as f32, pos_y as f32);
+        if let Some(levels) = video.as_mut().and_then(|v| v.take_audio_levels()) {
+            let _ = tx.send(ProjectorCommand::AudioLevels(levels));
+        }
 
-            //println!("Screen: {screen_width}x{screen_height}; scale: {scale}; video: {draw_width}x{draw_height}; pos: {pos_x}, {pos_y}");
+        // a muted or faded-out item is allowed to be quiet
+        let expecting_audio = level > 0.0 && video.as_ref().is_some_and(|v| v.has_audio());
+        let audible = video.as_ref().is_some_and(|v| v.is_audible());
+        if let Some(warning) = silence.update(expecting_audio, audible) {
+            let _ = tx.send(ProjectorCommand::SilenceWarning(warning));
+        }
 
-            d.draw_texture_ex(&v.frame_texture, pos, rotation, scale, Color::WHITE);
-            //d.draw_rectangle_v(pos, Vector2::new(draw_width, draw_height), Color::GREEN);
+        let preview_due = preview.is_due();
+        // one capture serves both, at the larger of their widths
+        let capture = [
+            pending_snapshot.map(|(max_width, _)| max_width),
+            preview_due.then(|| preview.max_width()),
+        ]
+        .into_iter()
+        .flatten()
+        .reduce(|a, b| if a == 0 || b == 0 { 0 } else { a.max(b) });
+        backend.present(Scene {
+            video: video.as_mut(),
+            config: &config,
+            show_subtitles,
+            show_stats,
+            show_test_grid,
+            addresses: if connected { &[] } else { addresses.as_slice() },
+            capture,
+        });
 
-            if v.is_finished() {
-                let _ = tx.send(ProjectorCommand::VideoEnded);
-                video = None;
+        let mut captured = capture.and_then(|_| backend.snapshot());
+        if preview_due {
+            // the frame is only shared when a snapshot was asked for at the same time
+            let frame = match pending_snapshot {
+                Some(_) => captured.clone(),
+                None => captured.take(),
+            };
+            if let Some(frame) = frame {
+                preview.send(frame, &tx);
             }
-        } else {
-            if (!connected) {
-                d.draw_fps(0, 0);
-
-                d.draw_text(format!("SimplePresenter Projector\n\nScreen: {screen_width}X{screen_height}").as_str(), 12, 12, 18, Color::WHITE);
-
-                let mut x = screen_width / 2.0 - 240.0;
-                let mut y = screen_height / 2.0 - 240.0;
-                d.draw_text("Server ready:", x as i32, y as i32, 18, Color::WHITE);
-                x += 20.0;
-                y += 24.0;
-                for (_, ip) in network_interfaces.iter() {
-                    d.draw_text(format!("ws://{ip:?}:8765").as_str(), x as i32, y as i32, 24, Color::WHITE);
-                    y += 24.0;
-                }
+        }
+        if let Some((max_width, format)) = pending_snapshot.take() {
+            if let Some(frame) = captured {
+                let tx = tx.clone();
+                std::thread::spawn(move || {
+                    let (width, height) = frame.scaled_size(max_width);
+                    match frame.encode(format, (width, height)) {
+                        Ok(data) => {
+                            let _ = tx.send(ProjectorCommand::SnapshotImage {
+                                format,
+                                width,
+                                height,
+                                data,
+                            });
+                        }
+                        Err(err) => eprintln!("Failed to encode snapshot: {}", err),
+                    }
+                });
             }
         }
 
+        if video.as_mut().is_some_and(|v| v.is_finished()) {
+            let _ = tx.send(ProjectorCommand::VideoEnded);
+            video = None;
+        }
     }
 
     Ok(())
 }
+
+/// Applies the cached loudness of the audio track that plays, or starts measuring it and
+/// returns the track to wait for
+fn normalize_loudness(
+    v: &mut RaylibVideo,
+    path: &str,
+    config: &ProjectorConfig,
+    probe_cache: &Arc<Mutex<ProbeCache>>,
+    cache_path: &Path,
+    tx: &UnboundedSender<ProjectorCommand>,
+) -> Option<(String, u32)> {
+    if !config.loudness.normalize || !v.has_audio() {
+        return None;
+    }
+
+    let audio_track = v.audio_track();
+    let cached = probe_cache.lock().ok().and_then(|c| c.loudness(path, audio_track));
+    match cached {
+        Some(lufs) => {
+            v.set_normalization_gain(normalization_gain(
+                lufs,
+                config.loudness.target,
+                config.loudness.max_gain_db,
+            ));
+            None
+        }
+        None => {
+            // unity gain until the track is measured
+            v.set_normalization_gain(1.0);
+            spawn_analysis(
+                probe_cache.clone(),
+                PathBuf::from(cache_path),
+                path.to_string(),
+                audio_track,
+                tx.clone(),
+            );
+            Some((path.to_string(), audio_track))
+        }
+    }
+}
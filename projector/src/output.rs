@@ -0,0 +1,221 @@
+use crate::config::WindowConfig;
+use anyhow::anyhow;
+use raylib::ffi;
+use raylib::prelude::RaylibHandle;
+use shared::Monitor;
+use std::env;
+use std::ffi::{c_int, c_void, CStr};
+use shared::ProjectorCommand;
+use std::io::Write;
+use std::process::{Child, Command, Stdio};
+
+// set on the projectors started for `outputs`, so their configs can't start more
+const OUTPUT_ENV: &str = "SIMPLEPRESENTER_OUTPUT";
+
+pub fn list_monitors() -> Vec<Monitor> {
+    // SAFETY: plain raylib query, the window is open while the backend exists
+    let count = unsafe { ffi::GetMonitorCount() };
+    (0..count)
+        // SAFETY: the index is below the monitor count, and the name GLFW returns stays
+        // valid until the monitor is disconnected, it is copied right away
+        .map(|index| unsafe {
+            let name = ffi::GetMonitorName(index);
+            let position = ffi::GetMonitorPosition(index);
+            Monitor {
+                index: index as u32,
+                name: if name.is_null() {
+                    String::new()
+                } else {
+                    CStr::from_ptr(name).to_string_lossy().into_owned()
+                },
+                width: ffi::GetMonitorWidth(index),
+                height: ffi::GetMonitorHeight(index),
+                x: position.x as i32,
+                y: position.y as i32,
+            }
+        })
+        .collect()
+}
+
+/// Moves the window to its monitor, centred or fullscreen at the monitor's resolution
+pub fn place_window(rl: &mut RaylibHandle, config: &WindowConfig) -> anyhow::Result<()> {
+    if rl.is_window_fullscreen() {
+        rl.toggle_fullscreen();
+    }
+
+    let monitors = list_monitors();
+    let monitor = match config.monitor {
+        Some(index) => Some(
+            monitors
+                .iter()
+                .find(|m| m.index == index)
+                .ok_or_else(|| anyhow!("There is no monitor {}", index))?,
+        ),
+        // SAFETY: plain raylib query on the open window
+        None => monitors.get(unsafe { ffi::GetCurrentMonitor() } as usize),
+    };
+    let Some(monitor) = monitor else {
+        rl.set_window_size(config.width, config.height);
+        return Ok(());
+    };
+
+    // fullscreen switches the monitor to the window size, so match it first
+    let (width, height) = if config.fullscreen {
+        (monitor.width, monitor.height)
+    } else {
+        (config.width, config.height)
+    };
+    rl.set_window_size(width, height);
+    rl.set_window_position(
+        monitor.x + (monitor.width - width).max(0) / 2,
+        monitor.y + (monitor.height - height).max(0) / 2,
+    );
+    if config.fullscreen {
+        rl.toggle_fullscreen();
+    }
+    Ok(())
+}
+
+// raylib bundles GLFW but doesn't wrap its video modes, these come from its copy
+#[repr(C)]
+struct GlfwVidMode {
+    width: c_int,
+    height: c_int,
+    red_bits: c_int,
+    green_bits: c_int,
+    blue_bits: c_int,
+    refresh_rate: c_int,
+}
+
+unsafe extern "C" {
+    fn glfwGetWindowMonitor(window: *mut c_void) -> *mut c_void;
+    fn glfwGetVideoMode(monitor: *mut c_void) -> *const GlfwVidMode;
+    fn glfwGetVideoModes(monitor: *mut c_void, count: *mut c_int) -> *const GlfwVidMode;
+    fn glfwSetWindowMonitor(
+        window: *mut c_void,
+        monitor: *mut c_void,
+        x: c_int,
+        y: c_int,
+        width: c_int,
+        height: c_int,
+        refresh_rate: c_int,
+    );
+}
+
+// a 24 Hz mode counts for 23.976 fps content
+const REFRESH_TOLERANCE: f64 = 0.005;
+
+/// Switches the fullscreen window's monitor to the highest refresh rate at the current
+/// resolution that shows every frame of `fps` content for the same number of refreshes.
+/// GLFW restores the desktop mode when the window leaves fullscreen or closes
+pub fn match_refresh_rate(fps: f64) -> Option<i32> {
+    // SAFETY: the window handle is raylib's open GLFW window, the monitor and its modes
+    // are owned by GLFW and stay valid while the window is fullscreen on it
+    unsafe {
+        let window = ffi::GetWindowHandle();
+        let monitor = glfwGetWindowMonitor(window);
+        // only fullscreen windows have a monitor whose mode they may change
+        if monitor.is_null() {
+            return None;
+        }
+        let current = glfwGetVideoMode(monitor).as_ref()?;
+        let mut count = 0;
+        let modes = glfwGetVideoModes(monitor, &mut count);
+        if modes.is_null() {
+            return None;
+        }
+
+        let rate = std::slice::from_raw_parts(modes, count.max(0) as usize)
+            .iter()
+            .filter(|mode| mode.width == current.width && mode.height == current.height)
+            .map(|mode| mode.refresh_rate)
+            .filter(|rate| suits_frame_rate(*rate, fps))
+            .max()?;
+        if rate != current.refresh_rate {
+            glfwSetWindowMonitor(window, monitor, 0, 0, current.width, current.height, rate);
+        }
+        Some(rate)
+    }
+}
+
+fn suits_frame_rate(rate: c_int, fps: f64) -> bool {
+    let repeats = (rate as f64 / fps).round();
+    fps > 0.0 && repeats >= 1.0 && (rate as f64 - repeats * fps).abs() <= rate as f64 * REFRESH_TOLERANCE
+}
+
+/// Whether this projector was started for another one's `outputs`
+pub fn is_extra_output() -> bool {
+    env::var_os(OUTPUT_ENV).is_some()
+}
+
+/// Projectors started for the extra output windows, stopped along with this one
+pub struct Outputs {
+    /// In `outputs` order, `None` where starting one failed
+    children: Vec<Option<Child>>,
+}
+
+impl Outputs {
+    /// raylib has a single window per process, so every extra output is a projector
+    /// process of its own with its own config and `listen` address. They get this
+    /// projector's `flags`, and commands for them through their stdin
+    pub fn spawn(configs: &[String], flags: &[String]) -> Self {
+        if is_extra_output() {
+            if !configs.is_empty() {
+                eprintln!("Ignoring the outputs of an output's config");
+            }
+            return Outputs {
+                children: Vec::new(),
+            };
+        }
+
+        let executable = match env::current_exe() {
+            Ok(executable) => executable,
+            Err(err) => {
+                eprintln!("Failed to find the projector executable: {}", err);
+                return Outputs {
+                    children: Vec::new(),
+                };
+            }
+        };
+
+        let children = configs
+            .iter()
+            .map(|config| {
+                Command::new(&executable)
+                    .arg(config)
+                    .args(flags)
+                    .env(OUTPUT_ENV, "1")
+                    .stdin(Stdio::piped())
+                    .spawn()
+                    .map_err(|e| eprintln!("Failed to start output {:?}: {}", config, e))
+                    .ok()
+            })
+            .collect();
+        Outputs { children }
+    }
+
+    /// Passes a command to an extra output, counting from 1 in `outputs` order
+    pub fn send(&mut self, output: u32, command: &ProjectorCommand) -> anyhow::Result<()> {
+        let child = (output as usize)
+            .checked_sub(1)
+            .and_then(|index| self.children.get_mut(index))
+            .and_then(Option::as_mut)
+            .ok_or_else(|| anyhow!("There is no output {}", output))?;
+        let stdin = child
+            .stdin
+            .as_mut()
+            .ok_or_else(|| anyhow!("Output {} takes no commands", output))?;
+        // messages span several lines, a blank line ends each
+        stdin.write_all(format!("{}\n\n", command.to_message()).as_bytes())?;
+        Ok(())
+    }
+}
+
+impl Drop for Outputs {
+    fn drop(&mut self) {
+        for child in self.children.iter_mut().flatten() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use crate::backend::Frame;
+use shared::{ImageFormat, ProjectorCommand};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc::UnboundedSender;
+
+/// Low resolution JPEG stream of the output for the GUI's confidence monitor
+pub struct PreviewStream {
+    max_width: u32,
+    interval: Option<Duration>,
+    next_capture: Instant,
+    // a frame is skipped while the previous one is still encoding, so the stream
+    // falls behind in framerate rather than in latency
+    encoding: Arc<AtomicBool>,
+}
+
+impl PreviewStream {
+    pub fn new() -> Self {
+        PreviewStream {
+            max_width: 0,
+            interval: None,
+            next_capture: Instant::now(),
+            encoding: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn start(&mut self, max_width: u32, fps: u32) {
+        self.max_width = max_width;
+        self.interval = Some(Duration::from_secs_f64(1.0 / fps.clamp(1, 30) as f64));
+        self.next_capture = Instant::now();
+    }
+
+    /// Width the frames are scaled to, 0 for full size
+    pub fn max_width(&self) -> u32 {
+        self.max_width
+    }
+
+    pub fn stop(&mut self) {
+        self.interval = None;
+    }
+
+    /// Whether the next presented frame should be captured for the stream
+    pub fn is_due(&self) -> bool {
+        self.interval.is_some()
+            && Instant::now() >= self.next_capture
+            && !self.encoding.load(Ordering::Acquire)
+    }
+
+    /// Encodes the captured frame off the render loop and sends it as a `PreviewFrame`
+    pub fn send(&mut self, frame: Frame, tx: &UnboundedSender<ProjectorCommand>) {
+        let Some(interval) = self.interval else {
+            return;
+        };
+        self.next_capture = Instant::now().max(self.next_capture + interval);
+
+        let captured_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_millis() as u64);
+        let (width, height) = frame.scaled_size(self.max_width);
+        let encoding = self.encoding.clone();
+        let tx = tx.clone();
+        encoding.store(true, Ordering::Release);
+        std::thread::spawn(move || {
+            match frame.encode(ImageFormat::Jpeg, (width, height)) {
+                Ok(data) => {
+                    let _ = tx.send(ProjectorCommand::PreviewFrame {
+                        width,
+                        height,
+                        captured_at,
+                        data,
+                    });
+                }
+                Err(err) => eprintln!("Failed to encode preview frame: {}", err),
+            }
+            encoding.store(false, Ordering::Release);
+        });
+    }
+}
@@ -0,0 +1,227 @@
+use anyhow::anyhow;
+use gstreamer::ClockTime;
+use gstreamer_pbutils::prelude::DiscovererStreamInfoExt;
+use gstreamer_pbutils::{Discoverer, DiscovererInfo};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use shared::path::path_to_file_uri;
+use shared::Track;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+const SIDECAR_SUBTITLE_EXTENSIONS: [&str; 2] = ["srt", "vtt"];
+
+/// Runs the GStreamer discoverer on a local file, returning its uri and stream info
+pub fn discover(path: &str) -> anyhow::Result<(String, DiscovererInfo)> {
+    if !Path::new(path).exists() {
+        return Err(anyhow!("Video file not found: {}", path));
+    }
+
+    let discoverer = Discoverer::new(ClockTime::from_seconds(5))
+        .map_err(|e| anyhow!("Failed to create GStreamer discoverer: {}", e))?;
+
+    let path_canonical = Path::new(path)
+        .canonicalize()
+        .map_err(|e| anyhow!("Failed to get canonical path for '{}': {}", path, e))?;
+
+    let uri =
+        path_to_file_uri(&path_canonical).map_err(|e| anyhow!("Failed to get uri: {}", e))?;
+    let info = discoverer
+        .discover_uri(&uri)
+        .map_err(|e| anyhow!("Failed to discover media information for '{}': {}", path, e))?;
+
+    Ok((uri, info))
+}
+
+/// Probes a file for the tracks a playlist item can choose from
+pub fn probe_tracks(path: &str) -> anyhow::Result<(Vec<Track>, Vec<Track>)> {
+    let (_, info) = discover(path)?;
+    Ok((audio_tracks(&info), subtitle_tracks(&info, Path::new(path))))
+}
+
+pub fn audio_tracks(info: &DiscovererInfo) -> Vec<Track> {
+    info.audio_streams()
+        .iter()
+        .enumerate()
+        .map(|(index, audio)| Track {
+            index: index as u32,
+            language: audio.language().map(|l| l.to_string()),
+            codec: media_type(audio.caps(), "audio/*"),
+            sidecar: None,
+            stream_id: audio.stream_id().map(|id| id.to_string()),
+        })
+        .collect()
+}
+
+/// Embedded subtitle streams first, followed by sidecar files next to the media
+pub fn subtitle_tracks(info: &DiscovererInfo, path: &Path) -> Vec<Track> {
+    let mut tracks: Vec<Track> = info
+        .subtitle_streams()
+        .iter()
+        .enumerate()
+        .map(|(index, subtitle)| Track {
+            index: index as u32,
+            language: subtitle.language().map(|l| l.to_string()),
+            codec: media_type(subtitle.caps(), "text/*"),
+            sidecar: None,
+            stream_id: subtitle.stream_id().map(|id| id.to_string()),
+        })
+        .collect();
+
+    for sidecar in sidecar_subtitles(path) {
+        tracks.push(Track {
+            index: tracks.len() as u32,
+            language: sidecar_language(path, &sidecar),
+            codec: sidecar
+                .extension()
+                .map(|e| e.to_string_lossy().to_lowercase())
+                .unwrap_or_default(),
+            sidecar: Some(sidecar.display().to_string()),
+            stream_id: None,
+        });
+    }
+
+    tracks
+}
+
+/// Finds `.srt`/`.vtt` files sharing the media's file stem, e.g. `clip.srt` or `clip.en.vtt`
+pub fn sidecar_subtitles(path: &Path) -> Vec<PathBuf> {
+    let (Some(dir), Some(stem)) = (path.parent(), path.file_stem()) else {
+        return Vec::new();
+    };
+    let stem = stem.to_string_lossy();
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+
+    let Ok(entries) = dir.read_dir() else {
+        return Vec::new();
+    };
+
+    let mut sidecars: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| {
+            let is_subtitle = p
+                .extension()
+                .map(|e| {
+                    SIDECAR_SUBTITLE_EXTENSIONS.contains(&e.to_string_lossy().to_lowercase().as_str())
+                })
+                .unwrap_or(false);
+            let name = p
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            is_subtitle && name.starts_with(&format!("{}.", stem))
+        })
+        .collect();
+
+    // keep the track order stable between probes
+    sidecars.sort();
+    sidecars
+}
+
+fn sidecar_language(media: &Path, sidecar: &Path) -> Option<String> {
+    let media_stem = media.file_stem()?.to_string_lossy().to_string();
+    let sidecar_stem = sidecar.file_stem()?.to_string_lossy().to_string();
+    let language = sidecar_stem.strip_prefix(&media_stem)?.strip_prefix('.')?;
+    (!language.is_empty()).then(|| language.to_string())
+}
+
+fn media_type(caps: Option<gstreamer::Caps>, fallback: &str) -> String {
+    caps.and_then(|caps| caps.structure(0).map(|s| s.name().to_string()))
+        .unwrap_or_else(|| fallback.to_string())
+}
+
+/// Metadata that is expensive to compute, kept on disk and keyed by file size and mtime
+/// so edited files get analysed again
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct ProbeCache {
+    #[serde(default)]
+    entries: Vec<ProbeCacheEntry>,
+    #[serde(skip)]
+    pending: HashSet<(String, u32)>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct ProbeCacheEntry {
+    path: String,
+    size: u64,
+    modified: u64,
+    /// Tracks of one file are measured separately
+    #[serde(default)]
+    audio_track: u32,
+    loudness: Option<f64>,
+}
+
+impl ProbeCache {
+    pub fn load(path: &Path) -> ProbeCache {
+        let Ok(content) = std::fs::read_to_string(path) else {
+            return ProbeCache::default();
+        };
+
+        toml::from_str(&content).unwrap_or_else(|e| {
+            warn!("Ignoring unreadable probe cache '{}': {}", path.display(), e);
+            ProbeCache::default()
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let content = toml::to_string(self)?;
+        std::fs::write(path, content)
+            .map_err(|e| anyhow!("Failed to write probe cache '{}': {}", path.display(), e))
+    }
+
+    pub fn loudness(&self, media: &str, audio_track: u32) -> Option<f64> {
+        let (size, modified) = file_identity(media)?;
+        self.entries
+            .iter()
+            .find(|e| {
+                e.path == media
+                    && e.audio_track == audio_track
+                    && e.size == size
+                    && e.modified == modified
+            })?
+            .loudness
+    }
+
+    pub fn set_loudness(&mut self, media: &str, audio_track: u32, lufs: f64) {
+        let Some((size, modified)) = file_identity(media) else {
+            return;
+        };
+
+        // other tracks measured before the file changed are outdated as well
+        self.entries.retain(|e| {
+            e.path != media || (e.audio_track != audio_track && e.size == size && e.modified == modified)
+        });
+        self.entries.push(ProbeCacheEntry {
+            path: media.to_string(),
+            size,
+            modified,
+            audio_track,
+            loudness: Some(lufs),
+        });
+    }
+
+    /// Marks a track as being analysed, returns false if it already is
+    pub fn begin_analysis(&mut self, media: &str, audio_track: u32) -> bool {
+        self.pending.insert((media.to_string(), audio_track))
+    }
+
+    pub fn end_analysis(&mut self, media: &str, audio_track: u32) {
+        self.pending.remove(&(media.to_string(), audio_track));
+    }
+}
+
+fn file_identity(path: &str) -> Option<(u64, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    let modified = metadata
+        .modified()
+        .ok()?
+        .duration_since(UNIX_EPOCH)
+        .ok()?
+        .as_secs();
+    Some((metadata.len(), modified))
+}
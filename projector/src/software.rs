@@ -0,0 +1,387 @@
+use crate::backend::Frame;
+use crate::layout::{Placement, Viewport};
+use crate::warp::warp_quads;
+use anyhow::anyhow;
+use raylib::ffi;
+use raylib::prelude::{Color, PixelFormat, Rectangle, Vector2};
+use shared::Warp;
+
+/// Output pixels nothing is drawn to
+const NONE: u32 = u32::MAX;
+// glyphs are rasterised this large and scaled down, like the window backend's fonts
+const FONT_BASE_SIZE: i32 = 96;
+// printable ASCII, what raylib loads without a codepoint list
+const FONT_GLYPHS: i32 = 95;
+
+/// For every output pixel the source pixel drawn there. Building it is the slow part, so
+/// it is kept until whatever it was built from changes
+pub struct PixelMap {
+    key: Vec<f32>,
+    table: Vec<u32>,
+}
+
+impl PixelMap {
+    pub fn new() -> Self {
+        PixelMap {
+            key: Vec::new(),
+            table: Vec::new(),
+        }
+    }
+
+    /// Nearest-neighbour map of a `source` sized frame drawn at `placement` through the
+    /// viewport, showing the `crop` part of the frame
+    pub fn placed(
+        &mut self,
+        output: (u32, u32),
+        source: (u32, u32),
+        placement: &Placement,
+        crop: &Rectangle,
+        viewport: &Viewport,
+    ) -> &[u32] {
+        let key = vec![
+            output.0 as f32,
+            output.1 as f32,
+            source.0 as f32,
+            source.1 as f32,
+            placement.center.x,
+            placement.center.y,
+            placement.size.x,
+            placement.size.y,
+            placement.rotation,
+            crop.x,
+            crop.y,
+            crop.width,
+            crop.height,
+            viewport.origin.0,
+            viewport.origin.1,
+            viewport.scale.0,
+            viewport.scale.1,
+        ];
+        if key != self.key {
+            self.table = placed_table(output, source, placement, crop, viewport);
+            self.key = key;
+        }
+        &self.table
+    }
+
+    /// Where each pixel of a `size` sized output comes from once warped
+    pub fn warped(&mut self, size: (u32, u32), warp: &Warp) -> &[u32] {
+        let mut key = vec![size.0 as f32, size.1 as f32, warp.columns as f32, warp.rows as f32];
+        key.extend(warp.corners.iter().chain(&warp.mesh).flat_map(|(x, y)| [*x, *y]));
+        if key != self.key {
+            self.table = warped_table(size, warp);
+            self.key = key;
+        }
+        &self.table
+    }
+}
+
+fn placed_table(
+    output: (u32, u32),
+    source: (u32, u32),
+    placement: &Placement,
+    crop: &Rectangle,
+    viewport: &Viewport,
+) -> Vec<u32> {
+    let mut table = vec![NONE; output.0 as usize * output.1 as usize];
+    if source.0 == 0 || source.1 == 0 {
+        return table;
+    }
+    // the frame is rotated clockwise about its centre, turn output points back
+    let (sin, cos) = (-placement.rotation.to_radians()).sin_cos();
+
+    for y in 0..output.1 {
+        let canvas_y = (y as f32 + 0.5) / viewport.scale.1 + viewport.origin.1;
+        for x in 0..output.0 {
+            let canvas_x = (x as f32 + 0.5) / viewport.scale.0 + viewport.origin.0;
+            let (dx, dy) = (canvas_x - placement.center.x, canvas_y - placement.center.y);
+            let u = (dx * cos - dy * sin) / placement.size.x + 0.5;
+            let v = (dx * sin + dy * cos) / placement.size.y + 0.5;
+            if !(0.0..1.0).contains(&u) || !(0.0..1.0).contains(&v) {
+                continue;
+            }
+
+            let source_x = ((crop.x + u * crop.width) * source.0 as f32) as u32;
+            let source_y = ((crop.y + v * crop.height) * source.1 as f32) as u32;
+            table[(y * output.0 + x) as usize] =
+                source_y.min(source.1 - 1) * source.0 + source_x.min(source.0 - 1);
+        }
+    }
+    table
+}
+
+/// Rasterises the warp's quads like the GPU does, two triangles each
+fn warped_table(size: (u32, u32), warp: &Warp) -> Vec<u32> {
+    let mut table = vec![NONE; size.0 as usize * size.1 as usize];
+    let (width, height) = (size.0 as f32, size.1 as f32);
+
+    for quad in warp_quads(warp) {
+        let [a, b, c, d] = quad.map(|((u, v), (x, y))| ((u, v), (x * width, y * height)));
+        for [p0, p1, p2] in [[a, b, c], [a, c, d]] {
+            let ((u0, v0), (x0, y0)) = p0;
+            let ((u1, v1), (x1, y1)) = p1;
+            let ((u2, v2), (x2, y2)) = p2;
+            let area = (x1 - x0) * (y2 - y0) - (x2 - x0) * (y1 - y0);
+            if area.abs() < f32::EPSILON {
+                continue;
+            }
+
+            let left = x0.min(x1).min(x2).floor().max(0.0) as u32;
+            let right = (x0.max(x1).max(x2).ceil().max(0.0) as u32).min(size.0);
+            let top = y0.min(y1).min(y2).floor().max(0.0) as u32;
+            let bottom = (y0.max(y1).max(y2).ceil().max(0.0) as u32).min(size.1);
+            for y in top..bottom {
+                for x in left..right {
+                    let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+                    let w1 = ((px - x0) * (y2 - y0) - (x2 - x0) * (py - y0)) / area;
+                    let w2 = ((x1 - x0) * (py - y0) - (px - x0) * (y1 - y0)) / area;
+                    let w0 = 1.0 - w1 - w2;
+                    // shared edges are claimed by both triangles rather than neither
+                    if w0 < -1e-4 || w1 < -1e-4 || w2 < -1e-4 {
+                        continue;
+                    }
+
+                    let u = (w0 * u0 + w1 * u1 + w2 * u2).clamp(0.0, 1.0);
+                    let v = (w0 * v0 + w1 * v1 + w2 * v2).clamp(0.0, 1.0);
+                    let source_x = ((u * width) as u32).min(size.0 - 1);
+                    let source_y = ((v * height) as u32).min(size.1 - 1);
+                    table[(y * size.0 + x) as usize] = source_y * size.0 + source_x;
+                }
+            }
+        }
+    }
+    table
+}
+
+/// Draws `source` through a map built for it, blending by its alpha
+pub fn draw_mapped(output: &mut Frame, source: &Frame, table: &[u32]) {
+    let pixels = output.rgba.chunks_exact_mut(4).zip(table);
+    for (target, &index) in pixels.filter(|(_, index)| **index != NONE) {
+        let index = index as usize * 4;
+        let pixel = &source.rgba[index..index + 4];
+        if pixel[3] == 255 {
+            target.copy_from_slice(pixel);
+        } else {
+            blend(target, [pixel[0], pixel[1], pixel[2]], pixel[3] as f32 / 255.0);
+        }
+    }
+}
+
+/// Copies `source` through a map built for it, leaving unmapped pixels black
+pub fn remap(source: &Frame, table: &[u32]) -> Frame {
+    let mut output = Frame::black(source.width, source.height);
+    draw_mapped(&mut output, source, table);
+    output
+}
+
+pub fn clear(frame: &mut Frame) {
+    for pixel in frame.rgba.chunks_exact_mut(4) {
+        pixel.copy_from_slice(&[0, 0, 0, 255]);
+    }
+}
+
+/// Multiplies the frame by a one byte per pixel mask, like the edge blend's blend mode
+pub fn multiply(frame: &mut Frame, mask: &[u8]) {
+    for (pixel, &gain) in frame.rgba.chunks_exact_mut(4).zip(mask) {
+        for channel in &mut pixel[..3] {
+            *channel = (*channel as u16 * gain as u16 / 255) as u8;
+        }
+    }
+}
+
+/// 5x5 gaussian blur, the same kernel as the window backend's blur shader, then `tint`
+pub fn blur(frame: &Frame, tint: Color) -> Frame {
+    let (width, height) = (frame.width as i32, frame.height as i32);
+    let mut output = Frame::black(frame.width, frame.height);
+    let tint = [tint.r, tint.g, tint.b].map(|c| c as f32 / 255.0);
+
+    for y in 0..height {
+        for x in 0..width {
+            let mut sum = [0.0f32; 3];
+            let mut total = 0.0;
+            for dy in -2..=2 {
+                for dx in -2..=2 {
+                    let weight = (-((dx * dx + dy * dy) as f32) / 4.0).exp();
+                    let sx = (x + dx).clamp(0, width - 1);
+                    let sy = (y + dy).clamp(0, height - 1);
+                    let index = (sy * width + sx) as usize * 4;
+                    for (channel, sum) in sum.iter_mut().enumerate() {
+                        *sum += frame.rgba[index + channel] as f32 * weight;
+                    }
+                    total += weight;
+                }
+            }
+            let index = (y * width + x) as usize * 4;
+            for channel in 0..3 {
+                output.rgba[index + channel] = (sum[channel] / total * tint[channel]) as u8;
+            }
+        }
+    }
+    output
+}
+
+/// One pixel wide line, like raylib's `draw_line_v`
+pub fn draw_line(frame: &mut Frame, start: Vector2, end: Vector2, color: Color) {
+    let steps = (end.x - start.x).abs().max((end.y - start.y).abs()).ceil().max(1.0) as u32;
+    for i in 0..=steps {
+        let t = i as f32 / steps as f32;
+        let point = start + (end - start) * t;
+        put_pixel(frame, point.x as i32, point.y as i32, color, 1.0);
+    }
+}
+
+pub fn draw_circle_lines(frame: &mut Frame, center: Vector2, radius: f32, color: Color) {
+    let steps = (radius * std::f32::consts::TAU).ceil().max(8.0) as u32;
+    for i in 0..steps {
+        let (sin, cos) = (i as f32 / steps as f32 * std::f32::consts::TAU).sin_cos();
+        let (x, y) = (center.x + cos * radius, center.y + sin * radius);
+        put_pixel(frame, x as i32, y as i32, color, 1.0);
+    }
+}
+
+fn put_pixel(frame: &mut Frame, x: i32, y: i32, color: Color, coverage: f32) {
+    if x < 0 || y < 0 || x >= frame.width as i32 || y >= frame.height as i32 {
+        return;
+    }
+    let index = (y as u32 * frame.width + x as u32) as usize * 4;
+    let alpha = coverage * color.a as f32 / 255.0;
+    blend(&mut frame.rgba[index..index + 4], [color.r, color.g, color.b], alpha);
+}
+
+fn blend(target: &mut [u8], color: [u8; 3], alpha: f32) {
+    for (channel, value) in target.iter_mut().zip(color) {
+        *channel = (value as f32 * alpha + *channel as f32 * (1.0 - alpha)).round() as u8;
+    }
+}
+
+/// A TTF/OTF font rasterised on the CPU. raylib's `Font` needs a texture, and with it a
+/// window, so only the glyph images are loaded and drawn by hand
+pub struct SoftFont {
+    glyphs: *mut ffi::GlyphInfo,
+    count: i32,
+}
+
+impl SoftFont {
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        let data = std::fs::read(path).map_err(|e| anyhow!("Failed to read font {:?}: {}", path, e))?;
+        // SAFETY: LoadFontData only reads the file data, the glyphs it returns are freed
+        // in `drop`
+        let glyphs = unsafe {
+            ffi::LoadFontData(
+                data.as_ptr(),
+                data.len() as i32,
+                FONT_BASE_SIZE,
+                std::ptr::null_mut(),
+                FONT_GLYPHS,
+                ffi::FontType::FONT_DEFAULT as i32,
+            )
+        };
+        if glyphs.is_null() {
+            return Err(anyhow!("Failed to load font {:?}", path));
+        }
+        Ok(SoftFont {
+            glyphs,
+            count: FONT_GLYPHS,
+        })
+    }
+
+    fn glyphs(&self) -> &[ffi::GlyphInfo] {
+        // SAFETY: LoadFontData returned `count` glyphs, alive until `drop`
+        unsafe { std::slice::from_raw_parts(self.glyphs, self.count as usize) }
+    }
+
+    // raylib falls back to '?' for characters the font doesn't have
+    fn glyph(&self, c: char) -> Option<&ffi::GlyphInfo> {
+        let glyphs = self.glyphs();
+        glyphs
+            .iter()
+            .find(|g| g.value == c as i32)
+            .or_else(|| glyphs.iter().find(|g| g.value == '?' as i32))
+    }
+
+    fn advance(glyph: &ffi::GlyphInfo) -> f32 {
+        if glyph.advanceX != 0 {
+            glyph.advanceX as f32
+        } else {
+            (glyph.image.width + glyph.offsetX) as f32
+        }
+    }
+
+    /// Width of one line, like raylib's `measure_text_ex`
+    pub fn measure(&self, text: &str, size: f32, spacing: f32) -> f32 {
+        let scale = size / FONT_BASE_SIZE as f32;
+        let glyphs: Vec<_> = text.chars().filter_map(|c| self.glyph(c)).collect();
+        let width: f32 = glyphs.iter().map(|g| Self::advance(g)).sum();
+        width * scale + glyphs.len().saturating_sub(1) as f32 * spacing
+    }
+
+    /// Draws one line with its top-left at `position`, like raylib's `draw_text_ex`
+    pub fn draw(
+        &self,
+        frame: &mut Frame,
+        text: &str,
+        position: Vector2,
+        size: f32,
+        spacing: f32,
+        color: Color,
+    ) {
+        let scale = size / FONT_BASE_SIZE as f32;
+        let mut x = position.x;
+        for glyph in text.chars().filter_map(|c| self.glyph(c)) {
+            let left = x + glyph.offsetX as f32 * scale;
+            let top = position.y + glyph.offsetY as f32 * scale;
+            draw_glyph(frame, &glyph.image, (left, top), scale, color);
+            x += Self::advance(glyph) * scale + spacing;
+        }
+    }
+}
+
+impl Drop for SoftFont {
+    fn drop(&mut self) {
+        // SAFETY: frees what LoadFontData allocated, once
+        unsafe { ffi::UnloadFontData(self.glyphs, self.count) };
+    }
+}
+
+/// Blends a glyph's coverage image onto the frame, scaled with bilinear filtering
+fn draw_glyph(frame: &mut Frame, image: &ffi::Image, position: (f32, f32), scale: f32, color: Color) {
+    let bytes_per_pixel = match image.format {
+        f if f == PixelFormat::PIXELFORMAT_UNCOMPRESSED_GRAYSCALE as i32 => 1,
+        f if f == PixelFormat::PIXELFORMAT_UNCOMPRESSED_GRAY_ALPHA as i32 => 2,
+        _ => return,
+    };
+    if image.data.is_null() || image.width <= 0 || image.height <= 0 {
+        return;
+    }
+    let (width, height) = (image.width as usize, image.height as usize);
+    // SAFETY: raylib glyph images hold width * height pixels in their format
+    let data = unsafe {
+        std::slice::from_raw_parts(image.data as *const u8, width * height * bytes_per_pixel)
+    };
+    // coverage is the last byte of a pixel, the alpha of grey-alpha images
+    let coverage = |x: usize, y: usize| {
+        data[(y.min(height - 1) * width + x.min(width - 1)) * bytes_per_pixel + bytes_per_pixel - 1]
+            as f32
+    };
+
+    let (left, top) = (position.0.floor() as i32, position.1.floor() as i32);
+    let right = (position.0 + width as f32 * scale).ceil() as i32;
+    let bottom = (position.1 + height as f32 * scale).ceil() as i32;
+    for y in top..bottom {
+        for x in left..right {
+            let gx = ((x as f32 + 0.5 - position.0) / scale - 0.5).max(0.0);
+            let gy = ((y as f32 + 0.5 - position.1) / scale - 0.5).max(0.0);
+            let (x0, y0) = (gx as usize, gy as usize);
+            if x0 >= width || y0 >= height {
+                continue;
+            }
+            let (fx, fy) = (gx.fract(), gy.fract());
+            let top_row = coverage(x0, y0) * (1.0 - fx) + coverage(x0 + 1, y0) * fx;
+            let bottom_row = coverage(x0, y0 + 1) * (1.0 - fx) + coverage(x0 + 1, y0 + 1) * fx;
+            let value = (top_row * (1.0 - fy) + bottom_row * fy) / 255.0;
+            if value > 0.0 {
+                put_pixel(frame, x, y, color, value);
+            }
+        }
+    }
+}
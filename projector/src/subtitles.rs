@@ -0,0 +1,272 @@
+use crate::config::{SubtitlePosition, SubtitleStyle};
+use anyhow::anyhow;
+use raylib::color::Color;
+use raylib::ffi;
+use raylib::math::Vector2;
+use raylib::prelude::RaylibDraw;
+use raylib::text::measure_text_ex;
+use std::path::Path;
+
+// Embedded cues without a duration stay up for this long
+const DEFAULT_CUE_DURATION_MS: u64 = 5000;
+
+#[derive(Debug, Clone)]
+pub struct Cue {
+    pub start_ms: u64,
+    pub end_ms: u64,
+    pub text: String,
+}
+
+impl Cue {
+    pub fn new(start_ms: u64, end_ms: Option<u64>, text: &str) -> Cue {
+        Cue {
+            start_ms,
+            end_ms: end_ms.unwrap_or(start_ms + DEFAULT_CUE_DURATION_MS),
+            text: strip_markup(text),
+        }
+    }
+}
+
+/// Returns the text of every cue active at `position_ms`, one per line
+pub fn active_text(cues: &[Cue], position_ms: u64) -> Option<String> {
+    let lines: Vec<&str> = cues
+        .iter()
+        .filter(|c| c.start_ms <= position_ms && position_ms < c.end_ms)
+        .map(|c| c.text.as_str())
+        .collect();
+
+    (!lines.is_empty()).then(|| lines.join("\n"))
+}
+
+/// Loads an `.srt` or `.vtt` file, both share the `start --> end` cue layout
+pub fn load_sidecar(path: &Path) -> anyhow::Result<Vec<Cue>> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| anyhow!("Failed to read subtitles '{}': {}", path.display(), e))?;
+    Ok(parse_cues(&content))
+}
+
+pub fn parse_cues(content: &str) -> Vec<Cue> {
+    let content = content.trim_start_matches('\u{feff}').replace("\r\n", "\n");
+    let mut cues = Vec::new();
+
+    for block in content.split("\n\n") {
+        let mut lines = block.lines().skip_while(|l| !l.contains("-->"));
+        let Some(timing) = lines.next() else {
+            // headers, numbering-only blocks, NOTE and STYLE blocks
+            continue;
+        };
+
+        let Some((start, end)) = timing.split_once("-->") else {
+            continue;
+        };
+        // WebVTT cue settings follow the end time
+        let end = end.split_whitespace().next().unwrap_or("");
+
+        let (Some(start_ms), Some(end_ms)) = (parse_timestamp(start.trim()), parse_timestamp(end))
+        else {
+            continue;
+        };
+
+        let text = lines.collect::<Vec<_>>().join("\n");
+        if !text.trim().is_empty() {
+            cues.push(Cue::new(start_ms, Some(end_ms), &text));
+        }
+    }
+
+    cues.sort_by_key(|c| c.start_ms);
+    cues
+}
+
+/// Parses `hh:mm:ss,mmm` (SRT) and `[hh:]mm:ss.mmm` (WebVTT)
+fn parse_timestamp(s: &str) -> Option<u64> {
+    let s = s.replace(',', ".");
+    let parts: Vec<&str> = s.split(':').collect();
+    let (hours, minutes, seconds) = match parts.as_slice() {
+        [h, m, s] => (h.parse::<u64>().ok()?, m.parse::<u64>().ok()?, *s),
+        [m, s] => (0, m.parse::<u64>().ok()?, *s),
+        _ => return None,
+    };
+
+    let (secs, millis) = seconds.split_once('.').unwrap_or((seconds, "0"));
+    let secs = secs.parse::<u64>().ok()?;
+    let millis = format!("{:0<3}", millis).get(..3)?.parse::<u64>().ok()?;
+
+    Some(((hours * 60 + minutes) * 60 + secs) * 1000 + millis)
+}
+
+/// Drops SRT/WebVTT/pango tags like `<i>` and ASS overrides like `{\an8}`
+fn strip_markup(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut closer = None;
+
+    for c in text.chars() {
+        match closer {
+            Some(end) if c == end => closer = None,
+            Some(_) => {}
+            None if c == '<' => closer = Some('>'),
+            None if c == '{' => closer = Some('}'),
+            None => out.push(c),
+        }
+    }
+
+    out.replace("&amp;", "&")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .trim()
+        .to_string()
+}
+
+// Directions the outline is drawn in, `outline` pixels away
+pub const OUTLINE_OFFSETS: [(f32, f32); 8] = [
+    (-1.0, -1.0),
+    (0.0, -1.0),
+    (1.0, -1.0),
+    (-1.0, 0.0),
+    (1.0, 0.0),
+    (-1.0, 1.0),
+    (0.0, 1.0),
+    (1.0, 1.0),
+];
+
+/// Where each subtitle line goes, inside the configured safe area
+pub struct SubtitleLayout<'a> {
+    pub font_size: f32,
+    pub spacing: f32,
+    pub lines: Vec<(&'a str, Vector2)>,
+    pub color: Color,
+    pub outline_color: Color,
+}
+
+/// Lays out centred subtitle lines, `measure` gives the width of a line at a font size
+/// and spacing
+pub fn layout_subtitles<'a>(
+    style: &SubtitleStyle,
+    text: &'a str,
+    screen_width: f32,
+    screen_height: f32,
+    measure: impl Fn(&str, f32, f32) -> f32,
+) -> SubtitleLayout<'a> {
+    let font_size = (style.size * screen_height).max(1.0);
+    let spacing = font_size / 10.0;
+    let line_height = font_size * 1.2;
+    let margin = style.safe_area * screen_height;
+
+    let block_height = line_height * text.lines().count() as f32;
+    let top = match style.position {
+        SubtitlePosition::Bottom => screen_height - margin - block_height,
+        SubtitlePosition::Top => margin,
+    };
+    let lines = text
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            let x = (screen_width - measure(line, font_size, spacing)) / 2.0;
+            (line, Vector2::new(x, top + line_height * i as f32))
+        })
+        .collect();
+
+    let [r, g, b, a] = style.color;
+    let [outline_r, outline_g, outline_b, outline_a] = style.outline_color;
+    SubtitleLayout {
+        font_size,
+        spacing,
+        lines,
+        color: Color::new(r, g, b, a),
+        outline_color: Color::new(outline_r, outline_g, outline_b, outline_a),
+    }
+}
+
+/// Draws centred, outlined subtitle lines inside the configured safe area
+pub fn draw_subtitles(
+    d: &mut impl RaylibDraw,
+    font: impl AsRef<ffi::Font> + Copy,
+    style: &SubtitleStyle,
+    text: &str,
+    screen_width: f32,
+    screen_height: f32,
+) {
+    let layout = layout_subtitles(style, text, screen_width, screen_height, |line, size, spacing| {
+        measure_text_ex(font, line, size, spacing).x
+    });
+    let (size, spacing) = (layout.font_size, layout.spacing);
+
+    for (line, position) in layout.lines {
+        if style.outline > 0.0 {
+            for (ox, oy) in OUTLINE_OFFSETS {
+                let pos = Vector2::new(position.x + ox * style.outline, position.y + oy * style.outline);
+                d.draw_text_ex(font, line, pos, size, spacing, layout.outline_color);
+            }
+        }
+        d.draw_text_ex(font, line, position, size, spacing, layout.color);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_srt() {
+        let srt = "\u{feff}1\r\n00:00:01,000 --> 00:00:02,500\r\n<i>Hello</i>\r\nworld\r\n\r\n\
+                   2\r\n01:02:03,040 --> 01:02:04,000\r\nSecond\r\n";
+        let cues = parse_cues(srt);
+
+        assert_eq!(cues.len(), 2);
+        assert_eq!((cues[0].start_ms, cues[0].end_ms), (1000, 2500));
+        assert_eq!(cues[0].text, "Hello\nworld");
+        assert_eq!((cues[1].start_ms, cues[1].end_ms), (3_723_040, 3_724_000));
+        assert_eq!(cues[1].text, "Second");
+    }
+
+    #[test]
+    fn parses_vtt() {
+        let vtt = "WEBVTT\n\nNOTE a comment\n\nSTYLE\n::cue { color: yellow }\n\n\
+                   intro\n00:05.000 --> 00:06.500 align:start line:0\n<v Roger>Hi</v>\n\n\
+                   00:00:01.250 --> 00:00:02.000\nFirst\n";
+        let cues = parse_cues(vtt);
+
+        assert_eq!(cues.len(), 2);
+        // sorted by start time
+        assert_eq!((cues[0].start_ms, cues[0].end_ms), (1250, 2000));
+        assert_eq!(cues[0].text, "First");
+        assert_eq!((cues[1].start_ms, cues[1].end_ms), (5000, 6500));
+        assert_eq!(cues[1].text, "Hi");
+    }
+
+    #[test]
+    fn skips_broken_and_empty_cues() {
+        let srt = "1\n00:00:01,000 --> soon\nBroken\n\n2\n00:00:02,000 --> 00:00:03,000\n  \n";
+        assert!(parse_cues(srt).is_empty());
+    }
+
+    #[test]
+    fn parses_timestamps() {
+        assert_eq!(parse_timestamp("00:00:01,5"), Some(1500));
+        assert_eq!(parse_timestamp("10:00.042"), Some(600_042));
+        assert_eq!(parse_timestamp("1:00:00"), Some(3_600_000));
+        assert_eq!(parse_timestamp("12"), None);
+        assert_eq!(parse_timestamp("aa:00.000"), None);
+    }
+
+    #[test]
+    fn strips_markup() {
+        assert_eq!(strip_markup("{\\an8}<b>Tom &amp; Jerry</b>"), "Tom & Jerry");
+        assert_eq!(strip_markup("<font color=\"red\">1 &lt; 2</font> "), "1 < 2");
+        assert_eq!(strip_markup("<c.yellow>a</c> {b} c"), "a  c");
+    }
+
+    #[test]
+    fn active_text_joins_overlapping_cues() {
+        let cues = vec![
+            Cue::new(0, Some(2000), "one"),
+            Cue::new(1000, Some(3000), "two"),
+            Cue::new(4000, None, "three"),
+        ];
+
+        assert_eq!(active_text(&cues, 1500).as_deref(), Some("one\ntwo"));
+        assert_eq!(active_text(&cues, 2000).as_deref(), Some("two"));
+        assert_eq!(active_text(&cues, 3500), None);
+        assert_eq!(active_text(&cues, 8999).as_deref(), Some("three"));
+        assert_eq!(active_text(&cues, 9000), None);
+    }
+}
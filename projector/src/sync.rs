@@ -0,0 +1,60 @@
+use crate::config::{SyncConfig, SyncRole};
+use gstreamer as gst;
+use gstreamer::prelude::*;
+use gstreamer_net as gst_net;
+
+/// Clock cued items are started on. The leader serves its system clock on the network
+/// and followers slave theirs to it; projectors on one machine already share the
+/// system clock
+pub struct SharedClock {
+    pub clock: gst::Clock,
+    // serves `clock` to the followers for as long as it lives
+    _provider: Option<gst_net::NetTimeProvider>,
+}
+
+impl SharedClock {
+    pub fn new(config: &SyncConfig) -> Self {
+        let system_clock = gst::SystemClock::obtain();
+        match config.role {
+            SyncRole::Off => SharedClock {
+                clock: system_clock,
+                _provider: None,
+            },
+            SyncRole::Leader => {
+                let provider =
+                    gst_net::NetTimeProvider::new(&system_clock, None, config.clock_port as i32);
+                println!("Serving the playback clock on port {}", config.clock_port);
+                SharedClock {
+                    clock: system_clock,
+                    _provider: Some(provider),
+                }
+            }
+            SyncRole::Follower => {
+                let clock = gst_net::NetClientClock::new(
+                    Some("projector-clock"),
+                    &config.leader,
+                    config.clock_port as i32,
+                    gst::ClockTime::ZERO,
+                );
+                println!(
+                    "Following the playback clock of {}:{}",
+                    config.leader, config.clock_port
+                );
+                SharedClock {
+                    clock: clock.upcast(),
+                    _provider: None,
+                }
+            }
+        }
+    }
+
+    /// Current time in nanoseconds, the time base of `StartAt`
+    pub fn time(&self) -> u64 {
+        self.clock.time().nseconds()
+    }
+
+    /// False until a follower has caught up with the leader
+    pub fn is_synced(&self) -> bool {
+        self.clock.is_synced()
+    }
+}
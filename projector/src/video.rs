@@ -1,22 +1,29 @@
 ﻿
 use anyhow::{anyhow, Error, Result};
 use fraction::Fraction;
-use gstreamer::{self as gst, SeekFlags};
-use gstreamer::{prelude::*, ClockTime};
+use gstreamer as gst;
+use gstreamer::prelude::*;
 use gstreamer_pbutils::prelude::DiscovererStreamInfoExt;
-use gstreamer_pbutils::Discoverer;
+use gstreamer_video::{VideoColorMatrix, VideoColorRange, VideoColorimetry, VideoInfo};
 use log::warn;
-use raylib::color::Color;
-use raylib::math::Vector2;
-use raylib::prelude::RaylibDraw;
-use raylib::texture::{Image, RaylibTexture2D, Texture2D};
+use raylib::prelude::{RaylibDraw, Rectangle};
 use raylib::{RaylibHandle, RaylibThread};
 use std::path::Path;
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
-use std::{env, fmt};
-use shared::path::path_to_file_uri;
+use std::time::{Duration, Instant};
+use shared::{AudioLevels, Background, ItemOptions, ScaleMode, Track};
+use crate::audio::{
+    build_audio_branch, fade_gain, levels_from_structure, make_level, make_volume, AudioOutput,
+    SILENCE_THRESHOLD_DB,
+};
+use crate::config::{ColorMatrix, ColorRange, ProjectorConfig, ScaleQuality, VideoConfig};
+use crate::frame::{FrameRenderer, FrameSlot, RGBA_FORMAT, YUV_FORMATS};
+use crate::layout::{place, Placement};
+use crate::probe::{audio_tracks, discover, subtitle_tracks};
+use crate::subtitles::{active_text, load_sidecar, Cue};
+
+const RESIZE_SETTLE_TIME: Duration = Duration::from_millis(250);
 
 #[derive(Debug)]
 pub struct AudioMetadata {
@@ -40,51 +47,67 @@ pub struct RaylibVideo {
     pub is_interlaced: bool,
     pub par: Fraction,
     pub media_type: String,
+    pub scale_mode: ScaleMode,
+    /// Clockwise degrees, see `layout::quarter_turns`
+    pub rotation: u32,
+    /// What fills the area the frame doesn't cover
+    pub background: Background,
+    /// Part of the frame that is shown, as fractions of its size
+    pub crop: Rectangle,
 
     pub audio_meta: Option<AudioMetadata>,
+    pub audio_tracks: Vec<Track>,
+    pub subtitle_tracks: Vec<Track>,
+    pub subtitle_track: Option<u32>,
+    subtitle_cues: Arc<Mutex<Vec<Cue>>>,
+
+    volume: gst::Element,
+    base_volume: f64,
+    normalization_gain: f64,
+    fade_in_ms: u32,
+    fade_out_ms: u32,
+    new_audio_levels: Option<AudioLevels>,
+    audible_at: Option<Instant>,
 
     pub timestamp_ms: Arc<AtomicU64>,
 
     paused: bool,
-    rate: f64,
+    /// Prerolled on the shared clock, waiting for `start_at`
+    cued: bool,
+    // after `start_at` the pipeline keeps its base time, so resumes pick a new one
+    // themselves, see `rebase`
+    synced: bool,
+    paused_running_time: Option<gst::ClockTime>,
 
     pipeline: gst::Pipeline,
+    decode: gst::Element,
+    /// Caps after `videoscale`, bounded by the output size when downscaling is enabled
+    scale_caps: gst::Element,
+    downscale: bool,
+    output_bounds: (u32, u32),
+    // a resize is applied once the window size stopped changing
+    pending_output_bounds: Option<((u32, u32), Instant)>,
+    stream_selection: Arc<Mutex<StreamSelection>>,
 
-    video_frame: Arc<Mutex<Vec<u8>>>,
-    video_frame_is_dirty: Arc<AtomicBool>,
+    frames: Arc<FrameSlot>,
+    has_video: bool,
+    /// Size and pixel aspect of the newest decoded frame
+    frame_format: Option<(u32, u32, f32)>,
 
-    // Raylib specific
-    pub frame_texture: Texture2D,
+    // Raylib specific, created by the window backend on the first frame
+    renderer: Option<FrameRenderer>,
 }
 
 impl RaylibVideo {
     pub(crate) fn new(
         path: &str,
-        rl: &mut RaylibHandle,
-        thread: &RaylibThread,
+        options: &ItemOptions,
+        config: &ProjectorConfig,
+        output_bounds: (u32, u32),
     ) -> anyhow::Result<RaylibVideo> {
         gst::init()?;
 
-        if !Path::new(path).exists() {
-            return Err(anyhow!("Video file not found: {}", path));
-        }
-
-        let discoverer = Discoverer::new(ClockTime::from_seconds(5))
-            .map_err(|e| anyhow!("Failed to create GStreamer discoverer: {}", e))?;
-
-        let path_canonical = Path::new(path)
-            .canonicalize()
-            .map_err(|e| anyhow!("Failed to get canonical path for '{}': {}", path, e))?;
-
-        let path_canonical_str = path_canonical
-            .to_str()
-            .ok_or_else(|| anyhow!("Path contains invalid Unicode: {:?}", path_canonical))?;
-
-        let uri = path_to_file_uri(&path_canonical)
-            .map_err(|e| anyhow!("Failed to get uri: {}", e))?;
-        let info = discoverer
-            .discover_uri(&uri)
-            .map_err(|e| anyhow!("Failed to discover media information for '{}': {}", path, e))?;
+        let (uri, info) = discover(path)?;
 
         let video_duration = info
             .duration()
@@ -93,8 +116,9 @@ impl RaylibVideo {
         let video_duration = Duration::from_millis(video_duration_msec);
 
         let video_streams = info.video_streams();
-        if video_streams.is_empty() {
-            return Err(anyhow!("No video streams found in '{}'", path));
+        let audio_streams = info.audio_streams();
+        if video_streams.is_empty() && audio_streams.is_empty() {
+            return Err(anyhow!("No audio or video streams found in '{}'", path));
         }
 
         if video_streams.len() > 1 {
@@ -105,58 +129,73 @@ impl RaylibVideo {
             );
         }
 
-        let video = &video_streams[0];
-        let framerate_frac = video.framerate();
-
-        if framerate_frac < 0.into() {
-            return Err(anyhow!("Invalid negative framerate in '{}'", path));
-        }
+        // Audio-only files keep these defaults and get no video branch
+        let mut video_width = 0;
+        let mut video_height = 0;
+        let mut video_bitrate = 0;
+        let mut video_max_bitrate = 0;
+        let mut video_depth = 0;
+        let mut video_is_interlaced = false;
+        let mut video_framerate = Fraction::new(0u32, 1u32);
+        let mut frame_duration = None;
+        let mut video_par = Fraction::new(1u32, 1u32);
+        let mut video_media_type = "video/*".to_string();
 
-        let video_width = video.width();
-        let video_height = video.height();
+        if let Some(video) = video_streams.first() {
+            let framerate_frac = video.framerate();
 
-        if video_width == 0 || video_height == 0 {
-            return Err(anyhow!(
-                "Invalid video dimensions ({}x{}) in '{}'",
-                video_width,
-                video_height,
-                path
-            ));
-        }
+            if framerate_frac < 0.into() {
+                return Err(anyhow!("Invalid negative framerate in '{}'", path));
+            }
 
-        let video_bitrate = video.bitrate();
-        let video_max_bitrate = video.max_bitrate();
-        let video_depth = video.depth();
-        let video_is_interlaced = video.is_interlaced();
-        let video_framerate =
-            Fraction::new(framerate_frac.numer() as u32, framerate_frac.denom() as u32);
-        let video_par = Fraction::new(video.par().numer() as u32, video.par().denom() as u32);
+            video_width = video.width();
+            video_height = video.height();
 
-        let mut video_media_type = "video/*".to_string();
+            if video_width == 0 || video_height == 0 {
+                return Err(anyhow!(
+                    "Invalid video dimensions ({}x{}) in '{}'",
+                    video_width,
+                    video_height,
+                    path
+                ));
+            }
 
-        if let Some(caps) = video.caps() {
-            if caps.iter().len() > 1 {
-                warn!("Video stream has multiple caps. Only the first one will be used.");
+            video_bitrate = video.bitrate();
+            video_max_bitrate = video.max_bitrate();
+            video_depth = video.depth();
+            video_is_interlaced = video.is_interlaced();
+            video_framerate =
+                Fraction::new(framerate_frac.numer() as u32, framerate_frac.denom() as u32);
+            if framerate_frac.numer() > 0 {
+                frame_duration = Some(Duration::from_secs_f64(
+                    framerate_frac.denom() as f64 / framerate_frac.numer() as f64,
+                ));
             }
+            video_par = Fraction::new(video.par().numer() as u32, video.par().denom() as u32);
+
+            if let Some(caps) = video.caps() {
+                if caps.iter().len() > 1 {
+                    warn!("Video stream has multiple caps. Only the first one will be used.");
+                }
 
-            for c in caps.iter() {
-                video_media_type = c.name().to_string();
-                break;
+                for c in caps.iter() {
+                    video_media_type = c.name().to_string();
+                    break;
+                }
             }
         }
 
-        let mut audio_info: Option<AudioMetadata> = None;
-        let audio_streams = info.audio_streams();
+        let audio_tracks = audio_tracks(&info);
+        let subtitle_tracks = subtitle_tracks(&info, Path::new(path));
 
-        if audio_streams.len() > 1 {
-            warn!(
-                "Video '{}' has {} audio streams. Only the first one will be used.",
-                path,
-                audio_streams.len()
-            );
-        }
+        let audio_track = options
+            .audio_track
+            .filter(|t| (*t as usize) < audio_streams.len())
+            .unwrap_or(0);
 
-        if let Some(audio) = audio_streams.get(0) {
+        let mut audio_info: Option<AudioMetadata> = None;
+
+        if let Some(audio) = audio_streams.get(audio_track as usize) {
             let mut info = AudioMetadata {
                 media_type: "audio/*".to_string(),
                 bitrate: audio.bitrate(),
@@ -181,44 +220,59 @@ impl RaylibVideo {
             audio_info = Some(info)
         }
         
-        let pipeline_str = format!(
-            "urisourcebin uri=\"{}\" name=src ! decodebin name=decode ! queue ! videoconvert ! video/x-raw,format=RGB,width={},height={},colorimetry=sRGB ! appsink name=appsink sync=true decode. ! queue ! audioconvert !volume volume=0.1 ! audioresample ! autoaudiosink",
-            uri, video_width, video_height
-        );
+        let pipeline = gst::Pipeline::new();
 
-        let pipeline = gstreamer::parse::launch(&pipeline_str)
-            .map_err(|e| anyhow!("Failed to create pipeline: {}", e))?;
-        let pipeline = pipeline
-            .downcast::<gst::Pipeline>()
-            .map_err(|_| anyhow!("Failed to downcast pipeline"))?;
+        // decodebin3 only exposes the streams we select, which lets tracks be switched live
+        let decode = gst::ElementFactory::make("uridecodebin3")
+            .name("decode")
+            .property("uri", uri.as_str())
+            .build()
+            .map_err(|e| anyhow!("Failed to create decoder: {}", e))?;
+        pipeline
+            .add(&decode)
+            .map_err(|e| anyhow!("Failed to add decoder to pipeline: {}", e))?;
 
-        // Get the appsink element
-        let appsink = pipeline
-            .by_name("appsink")
-            .ok_or_else(|| anyhow!("Failed to get appsink element"))?
-            .downcast::<gstreamer_app::AppSink>()
-            .map_err(|_| anyhow!("Failed to downcast appsink"))?;
+        // YUV is taken as decoded and converted on the GPU, RGBA is the fallback for
+        // formats the shader doesn't handle
+        let mut formats = Vec::new();
+        if config.video.yuv_upload {
+            formats.extend(YUV_FORMATS);
+        }
+        formats.push(RGBA_FORMAT);
+        let video_caps = gst::Caps::builder("video/x-raw")
+            .field("format", gst::List::new(formats))
+            .build();
+
+        let scale_caps = gst::ElementFactory::make("capsfilter")
+            .name("scale_caps")
+            .property(
+                "caps",
+                scaled_caps(config.video.downscale.then_some(output_bounds)),
+            )
+            .build()?;
+        let appsink = gstreamer_app::AppSink::builder()
+            .name("appsink")
+            .caps(&video_caps)
+            .sync(true)
+            .build();
+        // the sink hands frames over at their PTS on the pipeline clock, frames that are
+        // already a frame late are dropped instead of being shown late
+        if let Some(duration) = frame_duration {
+            appsink.set_property("max-lateness", duration.as_nanos() as i64);
+            appsink.set_property("qos", true);
+        }
 
         let weak_pipe = pipeline.downgrade();
         let ts_ref = Arc::new(AtomicU64::new(0));
         let ts_ref_clone = ts_ref.clone();
-        let frame_ref = Arc::new(Mutex::new(vec![
-            0;
-            video_width as usize
-                * video_height as usize
-                * 3
-        ]));
-        let frame_ref_clone = frame_ref.clone();
-        let dirtiness_ref = Arc::new(AtomicBool::new(false));
-        let dirtiness_ref_clone = dirtiness_ref.clone();
+        let frames = Arc::new(FrameSlot::new());
+        let frames_ref = frames.clone();
 
         // Set up appsink callbacks
         appsink.set_callbacks(
             gstreamer_app::AppSinkCallbacks::builder()
                 .new_sample(move |sink| {
                     let sample = sink.pull_sample().map_err(|_| gst::FlowError::Eos)?;
-                    let buffer = sample.buffer().ok_or(gst::FlowError::Error)?;
-                    let map = buffer.map_readable().map_err(|_| gst::FlowError::Error)?;
 
                     if let Some(pipeline) = weak_pipe.upgrade() {
                         if let Some(v) = pipeline.query_position::<gst::ClockTime>() {
@@ -226,23 +280,206 @@ impl RaylibVideo {
                         }
                     }
 
-                    // Lock the texture buffer for updating
-                    if let Ok(mut frame_data) = frame_ref_clone.lock() {
-                        frame_data.copy_from_slice(&map);
-                        dirtiness_ref_clone.store(true, Ordering::Relaxed);
-                    } else {
-                        eprintln!("Failed to lock texture buffer");
-                    }
+                    // the sample keeps the decoded buffer alive until it is uploaded
+                    frames_ref.publish(sample);
 
                     return Ok(gst::FlowSuccess::Ok);
                 })
                 .build(),
         );
 
+        // Embedded subtitles arrive ahead of time and are shown by position, so the
+        // sink neither syncs nor holds up prerolling on a sparse stream
+        let subtitle_cues = Arc::new(Mutex::new(Vec::new()));
+        let cues_ref = subtitle_cues.clone();
+        let textsink = gstreamer_app::AppSink::builder()
+            .name("textsink")
+            .caps(&gst::Caps::builder("text/x-raw").build())
+            .sync(false)
+            .build();
+        textsink.set_property("async", false);
+        textsink.set_callbacks(
+            gstreamer_app::AppSinkCallbacks::builder()
+                .new_sample(move |sink| {
+                    let sample = sink.pull_sample().map_err(|_| gst::FlowError::Eos)?;
+                    let buffer = sample.buffer().ok_or(gst::FlowError::Error)?;
+                    let Some(pts) = buffer.pts() else {
+                        return Ok(gst::FlowSuccess::Ok);
+                    };
+                    let map = buffer.map_readable().map_err(|_| gst::FlowError::Error)?;
+
+                    let end = buffer.duration().map(|d| (pts + d).mseconds());
+                    let cue = Cue::new(pts.mseconds(), end, &String::from_utf8_lossy(&map));
+                    if let Ok(mut cues) = cues_ref.lock() {
+                        // seeking back delivers the same cues again
+                        if !cues
+                            .iter()
+                            .any(|c| c.start_ms == cue.start_ms && c.text == cue.text)
+                        {
+                            cues.push(cue);
+                        }
+                    }
+
+                    Ok(gst::FlowSuccess::Ok)
+                })
+                .build(),
+        );
+
+        if let Some(sidecar) = sidecar_subtitle(&subtitle_tracks, options.subtitle_track) {
+            match load_sidecar(Path::new(&sidecar)) {
+                Ok(cues) => *subtitle_cues.lock().unwrap() = cues,
+                Err(err) => warn!("{}", err),
+            }
+        }
+
+        // Branches are only built for streams the discoverer reported, anything
+        // else (extra tracks, streams that show up later) is sent to a fakesink
+        // so it can't block prerolling
+        let has_video = !video_streams.is_empty();
+        let has_audio = !audio_streams.is_empty();
+        let video_linked = Arc::new(AtomicBool::new(false));
+        let audio_linked = Arc::new(AtomicBool::new(false));
+        let text_linked = Arc::new(AtomicBool::new(false));
+        // switching subtitles off removes the text pad, its branch is taken down so
+        // the textsink can take the next one
+        let text_branch: Arc<Mutex<Option<(gst::Pad, Vec<gst::Element>)>>> =
+            Arc::new(Mutex::new(None));
+        let text_linked_ref = text_linked.clone();
+        let text_branch_ref = text_branch.clone();
+        let weak_pipe = pipeline.downgrade();
+        let appsink_element = appsink.clone().upcast::<gst::Element>();
+        let scale_caps_element = scale_caps.clone();
+        let video_config = config.video.clone();
+        let deinterlace = options.deinterlace;
+        let textsink_element = textsink.clone().upcast::<gst::Element>();
+        let volume = make_volume(if options.fade_in_ms > 0 {
+            0.0
+        } else {
+            options.volume.unwrap_or(1.0)
+        })?;
+        let volume_element = volume.clone();
+        let level_element = make_level()?;
+        let audio_output = AudioOutput {
+            device: options
+                .audio_device
+                .clone()
+                .or_else(|| config.audio.device.clone()),
+            channel_map: config.audio.channel_map.clone(),
+        };
+
+        decode.connect_pad_added(move |_, src_pad| {
+            let Some(pipeline) = weak_pipe.upgrade() else {
+                return;
+            };
+
+            let media_type = pad_media_type(src_pad);
+
+            let result = if media_type.starts_with("video/")
+                && has_video
+                && !video_linked.swap(true, Ordering::SeqCst)
+            {
+                build_video_branch(
+                    deinterlace,
+                    scale_caps_element.clone(),
+                    &video_config,
+                    appsink_element.clone(),
+                )
+                .and_then(|branch| link_branch(&pipeline, src_pad, &branch))
+                .inspect_err(|_| video_linked.store(false, Ordering::SeqCst))
+            } else if media_type.starts_with("audio/")
+                && has_audio
+                && !audio_linked.swap(true, Ordering::SeqCst)
+            {
+                build_audio_branch(
+                    volume_element.clone(),
+                    level_element.clone(),
+                    &audio_output,
+                )
+                .and_then(|branch| link_branch(&pipeline, src_pad, &branch))
+                .inspect_err(|_| audio_linked.store(false, Ordering::SeqCst))
+            } else if media_type.starts_with("text/")
+                && !text_linked_ref.swap(true, Ordering::SeqCst)
+            {
+                build_text_branch(textsink_element.clone()).and_then(|branch| {
+                    link_branch(&pipeline, src_pad, &branch)?;
+                    if let Ok(mut linked) = text_branch_ref.lock() {
+                        *linked = Some((src_pad.clone(), branch));
+                    }
+                    Ok(())
+                })
+                .inspect_err(|_| text_linked_ref.store(false, Ordering::SeqCst))
+            } else {
+                build_discard_branch().and_then(|branch| link_branch(&pipeline, src_pad, &branch))
+            };
+
+            if let Err(err) = result {
+                warn!(
+                    "Failed to link {} stream from pad {}: {}",
+                    media_type,
+                    src_pad.name(),
+                    err
+                );
+                // an unlinked pad would fail the whole item with not-linked
+                if let Err(err) = build_discard_branch()
+                    .and_then(|branch| link_branch(&pipeline, src_pad, &branch))
+                {
+                    warn!("Failed to discard pad {}: {}", src_pad.name(), err);
+                }
+            }
+        });
+
+        let weak_pipe = pipeline.downgrade();
+        decode.connect_pad_removed(move |_, pad| {
+            let removed = text_branch
+                .lock()
+                .ok()
+                .and_then(|mut linked| linked.take_if(|(text_pad, _)| text_pad == pad));
+            let Some((_, branch)) = removed else {
+                return;
+            };
+            if let Some(pipeline) = weak_pipe.upgrade() {
+                for element in &branch {
+                    let _ = element.set_state(gst::State::Null);
+                    let _ = pipeline.remove(element);
+                }
+            }
+            text_linked.store(false, Ordering::SeqCst);
+        });
+
+        let stream_selection = Arc::new(Mutex::new(StreamSelection {
+            collection: None,
+            audio_track,
+            audio_stream: audio_tracks
+                .get(audio_track as usize)
+                .and_then(|t| t.stream_id.clone()),
+            text_stream: embedded_subtitle(&subtitle_tracks, options.subtitle_track),
+        }));
+
+        // Answering the collection from the streaming thread makes decodebin3 start with
+        // our tracks instead of its defaults
+        let bus = pipeline
+            .bus()
+            .ok_or_else(|| anyhow!("Pipeline has no bus"))?;
+        let selection_ref = stream_selection.clone();
+        let weak_decode = decode.downgrade();
+        bus.set_sync_handler(move |_, msg| {
+            if let gst::MessageView::StreamCollection(m) = msg.view() {
+                if let Ok(mut selection) = selection_ref.lock() {
+                    selection.collection = Some(m.stream_collection());
+                    if let Some(decode) = weak_decode.upgrade() {
+                        send_stream_selection(&decode, &selection);
+                    }
+                }
+            }
+            gst::BusSyncReply::Pass
+        });
+
         let video = RaylibVideo {
             pipeline,
             paused: true,
-            rate: 1.0,
+            cued: false,
+            synced: false,
+            paused_running_time: None,
             timestamp_ms: ts_ref,
             bitrate: video_bitrate,
             depth: video_depth,
@@ -254,12 +491,38 @@ impl RaylibVideo {
             max_bitrate: video_max_bitrate,
             media_type: video_media_type,
             par: video_par,
+            scale_mode: options.scale_mode.unwrap_or(config.video.scale_mode),
+            rotation: options.rotation.unwrap_or(config.video.rotation),
+            background: options
+                .background
+                .clone()
+                .unwrap_or_else(|| config.video.background.clone()),
+            crop: config.blend.crop(),
             audio_meta: audio_info,
+            audio_tracks,
+            subtitle_tracks,
+            subtitle_track: options.subtitle_track,
+            subtitle_cues,
+            volume,
+            base_volume: options.volume.unwrap_or(1.0),
+            normalization_gain: 1.0,
+            fade_in_ms: options.fade_in_ms,
+            fade_out_ms: options.fade_out_ms,
+            new_audio_levels: None,
+            audible_at: None,
 
-            video_frame: frame_ref,
-            video_frame_is_dirty: dirtiness_ref,
+            decode,
+            scale_caps,
+            downscale: config.video.downscale,
+            output_bounds,
+            pending_output_bounds: None,
+            stream_selection,
 
-            frame_texture: init_empty_texture(video_width, video_height, rl, thread)?,
+            frames,
+            has_video,
+            frame_format: None,
+
+            renderer: None,
         };
 
         return Ok(video);
@@ -267,98 +530,292 @@ impl RaylibVideo {
 
     pub(crate) fn play(&mut self) {
         self.paused = false;
+        // carry on from where it was paused
+        if let Some(running_time) = self.paused_running_time.take() {
+            self.rebase(running_time);
+        }
         if let Err(err) = self.pipeline.set_state(gst::State::Playing) {
             warn!("Error while changing pipeline state to Playing: {}", err)
         }
     }
 
+    /// Prerolls the item on `clock` so it can be started with `start_at`
+    pub(crate) fn cue(&mut self, clock: &gst::Clock) {
+        self.pipeline.use_clock(Some(clock));
+        self.cued = true;
+        self.pause();
+    }
+
+    /// Starts playing once `clock` reaches `base_time`, projectors started with the
+    /// same base time on a shared clock show the same frame at the same instant
+    pub(crate) fn start_at(&mut self, base_time: gst::ClockTime) {
+        if !self.cued {
+            warn!("Ignoring StartAt for an item that wasn't started with cue");
+            return;
+        }
+        self.cued = false;
+        self.synced = true;
+        // stop the pipeline from picking its own base time when it goes to Playing
+        self.pipeline.set_start_time(gst::ClockTime::NONE);
+        self.pipeline.set_base_time(base_time);
+        self.play();
+    }
+
+    /// Moves the base time so `running_time` is due now, for items started with
+    /// `start_at`. Other pipelines do this themselves
+    fn rebase(&self, running_time: gst::ClockTime) {
+        if !self.synced {
+            return;
+        }
+        if let Some(now) = self.pipeline.clock().map(|clock| clock.time()) {
+            self.pipeline.set_base_time(now.saturating_sub(running_time));
+        }
+    }
+
     fn pause(&mut self) {
+        if self.synced && !self.paused {
+            self.paused_running_time = self.pipeline.current_running_time();
+        }
         self.paused = true;
         if let Err(err) = self.pipeline.set_state(gst::State::Paused) {
             warn!("Error while changing pipeline state to Paused: {}", err)
         }
     }
 
-    fn seek(&self, time_ms: i64) {
-        let t = time_ms.clamp(0, self.duration.as_millis() as i64) as u64;
-        let target_ts = gst::ClockTime::from_mseconds(t);
-        self.timestamp_ms.store(t, Ordering::Relaxed);
+    /// Drains the bus, keeping track of audio levels, and reports whether the stream ended
+    pub(crate) fn is_finished(&mut self) -> bool {
+        let mut finished = false;
+        if let Some(bus) = self.pipeline.bus() {
+            while let Some(msg) = bus.pop() {
+                match msg.view() {
+                    gst::MessageView::Eos(_) => finished = true,
+                    gst::MessageView::Element(m) => {
+                        if let Some(levels) = m.structure().and_then(levels_from_structure) {
+                            self.record_levels(levels);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        finished
+    }
 
-        if let Err(err) = self.pipeline.seek(
-            self.rate,
-            SeekFlags::FLUSH | SeekFlags::KEY_UNIT | SeekFlags::TRICKMODE,
-            gstreamer::SeekType::Set,
-            target_ts,
-            gstreamer::SeekType::End,
-            ClockTime::ZERO,
-        ) {
-            warn!("Failed to set video rate: {}", err)
+    fn record_levels(&mut self, levels: AudioLevels) {
+        if levels.peak.iter().any(|p| *p > SILENCE_THRESHOLD_DB) {
+            self.audible_at = Some(Instant::now());
         }
-        return;
+        self.new_audio_levels = Some(levels);
     }
 
-    fn seek_relative(&self, time_ms: i64) {
-        self.seek(self.get_timestamp() as i64 + time_ms);
+    /// Levels measured since the last call
+    pub(crate) fn take_audio_levels(&mut self) -> Option<AudioLevels> {
+        self.new_audio_levels.take()
     }
 
-    pub(crate) fn is_finished(&self) -> bool {
-        if let Some(bus) = self.pipeline.bus() {
-            while let Some(msg) = bus.pop() {
-                if msg.type_() == gst::MessageType::Eos {
-                    return true;
+    pub(crate) fn has_audio(&self) -> bool {
+        self.audio_meta.is_some()
+    }
+
+    /// Whether anything above the silence threshold played within the last second
+    pub(crate) fn is_audible(&self) -> bool {
+        self.audible_at.is_some_and(|at| at.elapsed() < Duration::from_secs(1))
+    }
+
+    fn get_timestamp(&self) -> u64 {
+        return self.timestamp_ms.load(Ordering::Relaxed);
+    }
+
+    /// Renegotiates the decoded size to fit the render size, called every frame
+    pub(crate) fn set_output_size(&mut self, width: u32, height: u32) {
+        if !self.downscale || !self.has_video || width == 0 || height == 0 {
+            return;
+        }
+
+        let bounds = (width, height);
+        if bounds == self.output_bounds {
+            self.pending_output_bounds = None;
+            return;
+        }
+
+        match self.pending_output_bounds {
+            Some((pending, since)) if pending == bounds => {
+                if since.elapsed() >= RESIZE_SETTLE_TIME {
+                    // capsfilter sends a reconfigure upstream when its caps change
+                    self.scale_caps.set_property("caps", scaled_caps(Some(bounds)));
+                    self.output_bounds = bounds;
+                    self.pending_output_bounds = None;
                 }
             }
+            _ => self.pending_output_bounds = Some((bounds, Instant::now())),
         }
-        false
     }
 
-    pub(crate) fn wait_until_finished(&self) {
-        let bus = match self.pipeline.bus() {
-            Some(b) => b,
-            None => return,
+    /// Natural size of the video in square pixels. The aspect ratio follows the decoded
+    /// frames, which may change mid-stream, e.g. with adaptive streams or concatenated files
+    pub(crate) fn display_size(&self) -> (f32, f32) {
+        let aspect = match self.frame_format {
+            Some((width, height, pixel_aspect)) => width as f32 * pixel_aspect / height as f32,
+            None => {
+                let par = *self.par.numer().unwrap_or(&1) as f32
+                    / (*self.par.denom().unwrap_or(&1)).max(1) as f32;
+                self.width as f32 * par / self.height as f32
+            }
         };
-        for _ in bus.iter_timed(ClockTime::NONE) {}
+        // the decoded frames may already be downscaled, the source height is the natural one
+        (
+            self.height as f32 * aspect * self.crop.width,
+            self.height as f32 * self.crop.height,
+        )
     }
 
-    fn get_timestamp(&self) -> u64 {
-        return self.timestamp_ms.load(Ordering::Relaxed);
+    pub(crate) fn placement(&self, screen_width: f32, screen_height: f32) -> Placement {
+        place(
+            self.scale_mode,
+            self.rotation,
+            self.display_size(),
+            (screen_width, screen_height),
+        )
     }
 
-    pub(crate) fn update(&mut self) {
-        let dirty = self.video_frame_is_dirty.load(Ordering::Relaxed);
-        if !dirty {
+    /// The newest decoded frame, if there is one since the last call
+    pub(crate) fn take_frame(&mut self) -> Option<gst::Sample> {
+        let sample = self.frames.take()?;
+        if let Some(info) = sample.caps().and_then(|caps| VideoInfo::from_caps(caps).ok()) {
+            let par = info.par();
+            let pixel_aspect = par.numer() as f32 / par.denom().max(1) as f32;
+            self.frame_format = Some((info.width(), info.height(), pixel_aspect));
+        }
+        Some(sample)
+    }
+
+    /// Uploads the newest decoded frame into textures, if there is one
+    pub(crate) fn update(&mut self, rl: &mut RaylibHandle, thread: &RaylibThread) {
+        if !self.has_video {
             return;
         }
+        let Some(sample) = self.take_frame() else {
+            return;
+        };
 
-        self.video_frame_is_dirty.store(false, Ordering::Relaxed);
-        if let Ok(frame_data) = self.video_frame.lock() {
-            let result = self.frame_texture.update_texture(&frame_data);
-            if let Err(err) = result {
-                warn!("Failed to update video texture data: {}", err)
-            }
-        } else {
-            warn!("Failed to obtain video frame lock")
+        let renderer = self
+            .renderer
+            .get_or_insert_with(|| FrameRenderer::new(rl, thread));
+        if let Err(err) = renderer.upload(rl, thread, &sample) {
+            warn!("Failed to update video texture data: {}", err)
         }
     }
 
-    /// Setting rate too high might not reflect the actual playback rate
-    fn set_rate(&mut self, rate: f64) {
-        self.rate = rate.max(0.01);
-        let curr = self.get_timestamp();
-        if let Err(err) = self.pipeline.seek(
-            self.rate,
-            SeekFlags::FLUSH | SeekFlags::KEY_UNIT | SeekFlags::TRICKMODE,
-            gstreamer::SeekType::Set,
-            gst::ClockTime::from_mseconds(curr),
-            gstreamer::SeekType::End,
-            ClockTime::ZERO,
-        ) {
-            warn!("Failed to set video rate: {}", err)
+    /// Frames per second of the video stream, if it has a fixed rate
+    pub(crate) fn frame_rate(&self) -> Option<f64> {
+        let (numer, denom) = (*self.framerate.numer()?, *self.framerate.denom()?);
+        (self.has_video && numer > 0 && denom > 0).then(|| numer as f64 / denom as f64)
+    }
+
+    pub(crate) fn has_frame(&self) -> bool {
+        self.frame_format.is_some()
+    }
+
+    pub(crate) fn draw_frame<D: RaylibDraw>(&self, d: &mut D, placement: &Placement) {
+        if let Some(renderer) = &self.renderer {
+            renderer.draw(d, placement, &self.crop);
         }
     }
 
-    fn get_rate(&self) -> f64 {
-        return self.rate;
+    /// Upload timings and dropped frames, for the statistics overlay
+    pub(crate) fn frame_stats(&self) -> Option<String> {
+        let renderer = self.renderer.as_ref()?;
+        let (width, height) = renderer.size();
+        Some(format!(
+            "{}x{} upload {:.2} ms (avg {:.2} ms), {} dropped",
+            width,
+            height,
+            renderer.upload_time.as_secs_f64() * 1000.0,
+            renderer.average_upload_time.as_secs_f64() * 1000.0,
+            self.frames.dropped()
+        ))
+    }
+
+    /// Index of the audio track that plays
+    pub(crate) fn audio_track(&self) -> u32 {
+        self.stream_selection
+            .lock()
+            .map(|selection| selection.audio_track)
+            .unwrap_or(0)
+    }
+
+    pub(crate) fn select_audio_track(&mut self, track: u32) {
+        if track as usize >= self.audio_tracks.len() {
+            warn!("Audio track {} does not exist", track);
+            return;
+        }
+
+        if let Ok(mut selection) = self.stream_selection.lock() {
+            selection.audio_track = track;
+            selection.audio_stream = self.audio_tracks[track as usize].stream_id.clone();
+            send_stream_selection(&self.decode, &selection);
+        }
+    }
+
+    pub(crate) fn select_subtitle_track(&mut self, track: Option<u32>) {
+        if let Some(t) = track {
+            if t as usize >= self.subtitle_tracks.len() {
+                warn!("Subtitle track {} does not exist", t);
+                return;
+            }
+        }
+
+        self.subtitle_track = track;
+        if let Ok(mut cues) = self.subtitle_cues.lock() {
+            cues.clear();
+            if let Some(sidecar) = sidecar_subtitle(&self.subtitle_tracks, track) {
+                match load_sidecar(Path::new(&sidecar)) {
+                    Ok(sidecar_cues) => *cues = sidecar_cues,
+                    Err(err) => warn!("{}", err),
+                }
+            }
+        }
+
+        if let Ok(mut selection) = self.stream_selection.lock() {
+            selection.text_stream = embedded_subtitle(&self.subtitle_tracks, track);
+            send_stream_selection(&self.decode, &selection);
+        }
+    }
+
+    /// Applies the item gain, its fades and the master volume, returning the resulting gain
+    pub(crate) fn update_volume(&self, master_volume: f64, muted: bool) -> f64 {
+        let fade = fade_gain(
+            self.position_ms(),
+            self.duration.as_millis() as u64,
+            self.fade_in_ms,
+            self.fade_out_ms,
+        );
+        // the volume element tops out at 10x
+        let gain = (self.base_volume * self.normalization_gain * fade * master_volume).clamp(0.0, 10.0);
+
+        self.volume.set_property("volume", gain);
+        self.volume.set_property("mute", muted);
+
+        if muted { 0.0 } else { gain }
+    }
+
+    /// Loudness correction on top of the item volume, may arrive after playback started
+    pub(crate) fn set_normalization_gain(&mut self, gain: f64) {
+        self.normalization_gain = gain;
+    }
+
+    /// Subtitle text to show at the current playback position
+    pub(crate) fn subtitle_text(&self) -> Option<String> {
+        self.subtitle_track?;
+        let cues = self.subtitle_cues.lock().ok()?;
+        active_text(&cues, self.position_ms())
+    }
+
+    fn position_ms(&self) -> u64 {
+        self.pipeline
+            .query_position::<gst::ClockTime>()
+            .map(|p| p.mseconds())
+            .unwrap_or_else(|| self.get_timestamp())
     }
 }
 
@@ -368,24 +825,219 @@ impl Drop for RaylibVideo {
     }
 }
 
-fn init_empty_texture(
-    width: u32,
-    height: u32,
-    rl: &mut RaylibHandle,
-    t: &RaylibThread
-) -> Result<Texture2D, Error> {
-    let img = Image::gen_image_color(
-        width as i32,
-        height as i32,
-        raylib::ffi::Color {
-            r: 0,
-            g: 0,
-            b: 0,
-            a: 0
-        },
+struct StreamSelection {
+    collection: Option<gst::StreamCollection>,
+    audio_track: u32,
+    /// Stream ids the discoverer reported for the selected tracks
+    audio_stream: Option<String>,
+    /// Sidecar subtitles aren't part of the collection, so they leave this empty
+    text_stream: Option<String>,
+}
+
+fn embedded_subtitle(tracks: &[Track], track: Option<u32>) -> Option<String> {
+    let track = tracks.get(track? as usize)?;
+    track.sidecar.is_none().then(|| track.stream_id.clone()).flatten()
+}
+
+fn sidecar_subtitle(tracks: &[Track], track: Option<u32>) -> Option<String> {
+    tracks.get(track? as usize)?.sidecar.clone()
+}
+
+/// Asks decodebin3 for the first video stream plus the selected audio and text streams
+fn send_stream_selection(decode: &gst::Element, selection: &StreamSelection) {
+    let Some(collection) = &selection.collection else {
+        return;
+    };
+
+    let mut stream_ids = Vec::new();
+    let mut has_video = false;
+
+    for stream in collection.iter() {
+        let Some(stream_id) = stream.stream_id() else {
+            continue;
+        };
+
+        if stream.stream_type().contains(gst::StreamType::VIDEO) {
+            if !has_video {
+                stream_ids.push(stream_id.to_string());
+                has_video = true;
+            }
+        }
+    }
+
+    // the discoverer and decodebin3 read the same uri, so they agree on the ids
+    for wanted in [&selection.audio_stream, &selection.text_stream].into_iter().flatten() {
+        if collection.iter().any(|s| s.stream_id().as_deref() == Some(wanted.as_str())) {
+            stream_ids.push(wanted.clone());
+        } else {
+            warn!("Stream {} is not part of the collection", wanted);
+        }
+    }
+
+    let stream_ids: Vec<&str> = stream_ids.iter().map(|id| id.as_str()).collect();
+    if !decode.send_event(gst::event::SelectStreams::new(stream_ids)) {
+        warn!("Failed to select streams");
+    }
+}
+
+/// decodebin3 may expose a pad before its caps are set, so fall back to the stream type
+fn pad_media_type(pad: &gst::Pad) -> String {
+    if let Some(caps) = pad.current_caps() {
+        if let Some(s) = caps.structure(0) {
+            return s.name().to_string();
+        }
+    }
+
+    match pad.stream().map(|s| s.stream_type()) {
+        Some(t) if t.contains(gst::StreamType::VIDEO) => "video/*".to_string(),
+        Some(t) if t.contains(gst::StreamType::AUDIO) => "audio/*".to_string(),
+        Some(t) if t.contains(gst::StreamType::TEXT) => "text/*".to_string(),
+        _ => String::new(),
+    }
+}
+
+fn build_video_branch(
+    deinterlace: Option<bool>,
+    scale_caps: gst::Element,
+    config: &VideoConfig,
+    appsink: gst::Element,
+) -> Result<Vec<gst::Element>, Error> {
+    let method = match config.scale_quality {
+        ScaleQuality::Fast => "nearest-neighbour",
+        ScaleQuality::Good => "bilinear",
+        ScaleQuality::Best => "lanczos",
+    };
+    // "auto" passes progressive frames through untouched
+    let deinterlace_mode = match deinterlace {
+        None => "auto",
+        Some(true) => "interlaced",
+        Some(false) => "disabled",
+    };
+
+    let convert = gst::ElementFactory::make("videoconvert").build()?;
+    if config.matrix != ColorMatrix::Auto || config.range != ColorRange::Auto {
+        let (matrix, range) = (config.matrix, config.range);
+        convert
+            .static_pad("sink")
+            .ok_or_else(|| anyhow!("videoconvert has no sink pad"))?
+            .add_probe(gst::PadProbeType::EVENT_DOWNSTREAM, move |_, info| {
+                if let Some(gst::PadProbeData::Event(event)) = &mut info.data {
+                    let caps = match event.view() {
+                        gst::EventView::Caps(c) => override_colorimetry(c.caps(), matrix, range),
+                        _ => None,
+                    };
+                    if let Some(caps) = caps {
+                        *event = gst::event::Caps::new(&caps);
+                    }
+                }
+                gst::PadProbeReturn::Ok
+            });
+    }
+
+    // fields have to be separated before anything scales the frame vertically
+    Ok(vec![
+        gst::ElementFactory::make("queue").build()?,
+        gst::ElementFactory::make("deinterlace")
+            .property_from_str("mode", deinterlace_mode)
+            .build()?,
+        convert,
+        gst::ElementFactory::make("videoscale")
+            .property_from_str("method", method)
+            .build()?,
+        scale_caps,
+        appsink,
+    ])
+}
+
+/// Retags mis-tagged sources, both the RGBA conversion and the YUV shader read the
+/// colorimetry from the caps
+fn override_colorimetry(
+    caps: &gst::CapsRef,
+    matrix: ColorMatrix,
+    range: ColorRange,
+) -> Option<gst::Caps> {
+    let info = VideoInfo::from_caps(caps).ok()?;
+    let colorimetry = info.colorimetry();
+
+    let matrix = match matrix {
+        ColorMatrix::Auto => colorimetry.matrix(),
+        ColorMatrix::Bt601 => VideoColorMatrix::Bt601,
+        ColorMatrix::Bt709 => VideoColorMatrix::Bt709,
+        ColorMatrix::Bt2020 => VideoColorMatrix::Bt2020,
+    };
+    let range = match range {
+        ColorRange::Auto => colorimetry.range(),
+        ColorRange::Limited => VideoColorRange::Range16235,
+        ColorRange::Full => VideoColorRange::Range0255,
+    };
+    let colorimetry = VideoColorimetry::new(
+        range,
+        matrix,
+        colorimetry.transfer(),
+        colorimetry.primaries(),
     );
 
-    let mut texture = rl.load_texture_from_image(&t, &img)?;
-    texture.format = 4; // PIXELFORMAT_UNCOMPRESSED_R8G8B8
-    return Ok(texture);
+    let mut caps = caps.to_owned();
+    caps.make_mut().set("colorimetry", colorimetry.to_string());
+    Some(caps)
+}
+
+/// Only bounds the size: videoscale fixates to the source size when it fits and keeps
+/// the aspect ratio otherwise, so a caps change mid-stream renegotiates by itself
+fn scaled_caps(bounds: Option<(u32, u32)>) -> gst::Caps {
+    let mut caps = gst::Caps::builder("video/x-raw");
+    if let Some((width, height)) = bounds {
+        caps = caps
+            .field("width", gst::IntRange::new(1, width as i32))
+            .field("height", gst::IntRange::new(1, height as i32));
+    }
+    caps.build()
+}
+
+fn build_text_branch(textsink: gst::Element) -> Result<Vec<gst::Element>, Error> {
+    Ok(vec![gst::ElementFactory::make("queue").build()?, textsink])
+}
+
+pub(crate) fn build_discard_branch() -> Result<Vec<gst::Element>, Error> {
+    Ok(vec![
+        gst::ElementFactory::make("fakesink")
+            .property("sync", false)
+            .property("async", false)
+            .build()?,
+    ])
+}
+
+/// Adds a chain of elements to a running pipeline and links `src_pad` to its head.
+/// On failure the elements are taken out again, so they can be linked to another pad
+pub(crate) fn link_branch(
+    pipeline: &gst::Pipeline,
+    src_pad: &gst::Pad,
+    branch: &[gst::Element],
+) -> Result<(), Error> {
+    let head = branch
+        .first()
+        .ok_or_else(|| anyhow!("Cannot link an empty branch"))?;
+
+    pipeline.add_many(branch)?;
+    let linked = (|| -> Result<(), Error> {
+        gst::Element::link_many(branch)?;
+
+        for element in branch {
+            element.sync_state_with_parent()?;
+        }
+
+        let sink_pad = head
+            .static_pad("sink")
+            .ok_or_else(|| anyhow!("Branch head has no sink pad"))?;
+        src_pad.link(&sink_pad)?;
+        Ok(())
+    })();
+
+    if linked.is_err() {
+        for element in branch {
+            let _ = element.set_state(gst::State::Null);
+            let _ = pipeline.remove(element);
+        }
+    }
+    linked
 }
@@ -0,0 +1,142 @@
+use raylib::ffi;
+use raylib::prelude::*;
+use shared::Warp;
+
+// quads per side the output is split into, enough for the perspective of a corner pin
+// to look straight
+const SUBDIVISIONS: u32 = 32;
+const GRID_CELLS: i32 = 16;
+
+/// Renders the output into a texture and draws it back corner-pinned and mesh-warped.
+/// Does nothing while the warp is the identity
+pub struct WarpRenderer {
+    target: Option<(RenderTexture2D, (i32, i32))>,
+    active: bool,
+}
+
+impl WarpRenderer {
+    pub fn new() -> Self {
+        WarpRenderer {
+            target: None,
+            active: false,
+        }
+    }
+
+    /// Creates the output texture, has to run before drawing starts
+    pub fn prepare(
+        &mut self,
+        rl: &mut RaylibHandle,
+        thread: &RaylibThread,
+        warp: &Warp,
+        screen: (i32, i32),
+    ) {
+        if warp.is_identity() {
+            self.target = None;
+            return;
+        }
+        if self.target.as_ref().is_some_and(|(_, size)| *size == screen) {
+            return;
+        }
+
+        match rl.load_render_texture(thread, screen.0.max(1) as u32, screen.1.max(1) as u32) {
+            Ok(target) => {
+                let texture: ffi::Texture2D = *target.texture().as_ref();
+                // SAFETY: plain raylib call on a texture we own
+                unsafe {
+                    ffi::SetTextureFilter(texture, ffi::TextureFilter::TEXTURE_FILTER_BILINEAR as i32)
+                };
+                self.target = Some((target, screen));
+            }
+            Err(err) => eprintln!("Failed to create warp target: {}", err),
+        }
+    }
+
+    /// Redirects everything drawn until `end` into the output texture
+    pub fn begin(&mut self, d: &mut RaylibDrawHandle) {
+        let Some((target, _)) = &self.target else {
+            return;
+        };
+        let target: ffi::RenderTexture2D = *target.as_ref();
+        // SAFETY: texture mode is ended in `end` before the draw handle is dropped
+        unsafe { ffi::BeginTextureMode(target) };
+        d.clear_background(Color::BLACK);
+        self.active = true;
+    }
+
+    /// Draws the output texture to the screen through the warp
+    pub fn end(&mut self, warp: &Warp) {
+        if !std::mem::take(&mut self.active) {
+            return;
+        }
+        let Some((target, (width, height))) = &self.target else {
+            return;
+        };
+        let texture: ffi::Texture2D = *target.texture().as_ref();
+        let screen = (*width as f32, *height as f32);
+
+        // SAFETY: immediate mode drawing of a texture we own, the texture is unbound
+        // again before returning
+        unsafe {
+            ffi::EndTextureMode();
+            ffi::rlCheckRenderBatchLimit((SUBDIVISIONS * SUBDIVISIONS * 4) as i32);
+            ffi::rlSetTexture(texture.id);
+            ffi::rlBegin(ffi::RL_QUADS as i32);
+            ffi::rlColor4ub(255, 255, 255, 255);
+            for quad in warp_quads(warp) {
+                for ((u, v), (x, y)) in quad {
+                    // render textures are stored upside down
+                    ffi::rlTexCoord2f(u, 1.0 - v);
+                    ffi::rlVertex2f(x * screen.0, y * screen.1);
+                }
+            }
+            ffi::rlEnd();
+            ffi::rlSetTexture(0);
+        }
+    }
+}
+
+/// The output split into small quads, each corner as the image point `(u, v)` and where
+/// it lands `(x, y)`, all as fractions of the output size. Counter-clockwise like raylib's
+/// own quads
+pub fn warp_quads(warp: &Warp) -> impl Iterator<Item = [((f32, f32), (f32, f32)); 4]> + '_ {
+    let step = 1.0 / SUBDIVISIONS as f32;
+    (0..SUBDIVISIONS * SUBDIVISIONS).map(move |i| {
+        let (u, v) = ((i % SUBDIVISIONS) as f32 * step, (i / SUBDIVISIONS) as f32 * step);
+        [(u, v), (u, v + step), (u + step, v + step), (u + step, v)]
+            .map(|(u, v)| ((u, v), warp.map(u, v)))
+    })
+}
+
+/// Lines, diagonals and a circle to line the warp up against the screen
+pub fn draw_test_grid(d: &mut RaylibDrawHandle, width: f32, height: f32) {
+    let (lines, (center, radius)) = test_grid(width, height);
+    for (start, end, color) in lines {
+        d.draw_line_v(start, end, color);
+    }
+    d.draw_circle_lines(center.x as i32, center.y as i32, radius, Color::RED);
+}
+
+/// The test grid's lines and its red circle, for backends drawing it themselves
+pub fn test_grid(width: f32, height: f32) -> (Vec<(Vector2, Vector2, Color)>, (Vector2, f32)) {
+    let mut lines = Vec::new();
+    for i in 0..=GRID_CELLS {
+        let x = (width - 1.0) * i as f32 / GRID_CELLS as f32;
+        let y = (height - 1.0) * i as f32 / GRID_CELLS as f32;
+        lines.push((Vector2::new(x, 0.0), Vector2::new(x, height), Color::WHITE));
+        lines.push((Vector2::new(0.0, y), Vector2::new(width, y), Color::WHITE));
+    }
+    let (right, bottom) = (width - 1.0, height - 1.0);
+    for (start, end) in [
+        // the outline
+        ((0.0, 0.0), (right, 0.0)),
+        ((right, 0.0), (right, bottom)),
+        ((right, bottom), (0.0, bottom)),
+        ((0.0, bottom), (0.0, 0.0)),
+        // the diagonals
+        ((0.0, 0.0), (width, height)),
+        ((width, 0.0), (0.0, height)),
+    ] {
+        lines.push((Vector2::new(start.0, start.1), Vector2::new(end.0, end.1), Color::RED));
+    }
+    (lines, (Vector2::new(width / 2.0, height / 2.0), height / 4.0))
+}
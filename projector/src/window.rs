@@ -0,0 +1,229 @@
+use crate::backend::{BackendEvent, Frame, RenderBackend, Scene};
+use crate::background::BackgroundRenderer;
+use crate::blend::EdgeBlend;
+use crate::capture::ScreenCapture;
+use crate::config::{FramePacing, ProjectorConfig, WindowConfig};
+use crate::output::{list_monitors, match_refresh_rate, place_window};
+use crate::subtitles::draw_subtitles;
+use crate::warp::{draw_test_grid, WarpRenderer};
+use raylib::ffi::KeyboardKey;
+use raylib::prelude::*;
+use shared::Monitor;
+
+/// Renders to the projector's raylib window
+pub struct WindowBackend {
+    rl: RaylibHandle,
+    thread: RaylibThread,
+    background: BackgroundRenderer,
+    warp: WarpRenderer,
+    edge_blend: EdgeBlend,
+    capture: ScreenCapture,
+    default_font: WeakFont,
+    subtitle_font: Option<Font>,
+    captured: Option<Frame>,
+}
+
+impl WindowBackend {
+    pub fn new(config: &ProjectorConfig) -> Self {
+        let mut builder = raylib::init();
+        builder
+            .size(config.window.width, config.window.height)
+            .resizable()
+            .title("SimplePresenter Projector");
+        if config.video.vsync {
+            builder.vsync();
+        }
+        let (mut rl, thread) = builder.build();
+
+        for monitor in list_monitors() {
+            println!(
+                "Monitor {}: {} {}x{} at {},{}",
+                monitor.index, monitor.name, monitor.width, monitor.height, monitor.x, monitor.y
+            );
+        }
+        if let Err(err) = place_window(&mut rl, &config.window) {
+            eprintln!("Failed to place the output window: {}", err);
+        }
+
+        // with content pacing the main loop waits for frames itself
+        match config.video.pacing {
+            FramePacing::Content => rl.set_target_fps(0),
+            FramePacing::Fixed => rl.set_target_fps(config.video.target_fps.max(1)),
+        }
+
+        let background = BackgroundRenderer::new(&mut rl, &thread);
+        let default_font = rl.get_font_default();
+        // loaded large so it stays sharp when scaled to the output height
+        let subtitle_font = config.subtitles.font.as_ref().and_then(|font| {
+            rl.load_font_ex(&thread, font, 96, None)
+                .map_err(|e| eprintln!("Failed to load subtitle font {:?}: {}", font, e))
+                .ok()
+        });
+
+        WindowBackend {
+            rl,
+            thread,
+            background,
+            warp: WarpRenderer::new(),
+            edge_blend: EdgeBlend::new(),
+            capture: ScreenCapture::new(),
+            default_font,
+            subtitle_font,
+            captured: None,
+        }
+    }
+}
+
+impl RenderBackend for WindowBackend {
+    fn should_close(&self) -> bool {
+        self.rl.window_should_close()
+    }
+
+    fn events(&mut self) -> Vec<BackendEvent> {
+        let mut events = Vec::new();
+        if let Some(key) = self.rl.get_key_pressed() {
+            match key {
+                KeyboardKey::KEY_F11 => {
+                    self.rl.toggle_fullscreen();
+                }
+                KeyboardKey::KEY_F3 => {
+                    events.push(BackendEvent::ToggleStats);
+                }
+                _ => {}
+            }
+        }
+        events
+    }
+
+    fn screen_size(&self) -> (i32, i32) {
+        (self.rl.get_render_width(), self.rl.get_render_height())
+    }
+
+    fn monitors(&self) -> Vec<Monitor> {
+        list_monitors()
+    }
+
+    fn place_window(&mut self, window: &WindowConfig) -> anyhow::Result<()> {
+        place_window(&mut self.rl, window)
+    }
+
+    fn match_refresh_rate(&mut self, fps: f64) {
+        match match_refresh_rate(fps) {
+            Some(rate) => println!("Display refresh at {} Hz for {:.3} fps", rate, fps),
+            None => eprintln!("No fullscreen refresh rate suits {:.3} fps", fps),
+        }
+    }
+
+    fn present(&mut self, scene: Scene<'_>) {
+        let Scene {
+            mut video,
+            config,
+            show_subtitles,
+            show_stats,
+            show_test_grid,
+            addresses,
+            capture,
+        } = scene;
+        let (rl, thread) = (&mut self.rl, &self.thread);
+
+        // a video wall lays everything out on its canvas, of which this is one screen
+        let viewport = config
+            .wall
+            .viewport((rl.get_render_width() as f32, rl.get_render_height() as f32));
+
+        if let Some(v) = video.as_deref_mut() {
+            // decode at the size the frame takes up, which may exceed the window when filling
+            let placement = v.placement(viewport.canvas.0, viewport.canvas.1);
+            v.set_output_size(
+                (placement.size.x * viewport.scale.0).ceil() as u32,
+                (placement.size.y * viewport.scale.1).ceil() as u32,
+            );
+            v.update(rl, thread);
+
+            let (canvas_width, canvas_height) = viewport.canvas_pixels();
+            let canvas = (canvas_width as i32, canvas_height as i32);
+            self.background.prepare(rl, thread, &v.background, canvas);
+        }
+        let screen = (rl.get_render_width(), rl.get_render_height());
+        self.warp.prepare(rl, thread, &config.warp, screen);
+        self.edge_blend.prepare(rl, thread, &config.blend, screen);
+        if let Some(max_width) = capture {
+            self.capture.prepare(rl, thread, screen, max_width);
+        }
+
+        let mut d = rl.begin_drawing(thread);
+        d.clear_background(Color::BLACK);
+
+        let screen_width = d.get_render_width() as f32;
+        let screen_height = d.get_render_height() as f32;
+
+        if let Some(v) = video.as_deref().filter(|v| v.has_frame()) {
+            self.background.render(&mut d, thread, v);
+        }
+        self.warp.begin(&mut d);
+
+        if let Some(v) = video.as_deref() {
+            let (canvas_width, canvas_height) = viewport.canvas;
+            viewport.begin();
+            if v.has_frame() {
+                self.background.draw(&mut d, v, viewport.canvas);
+                let placement = v.placement(canvas_width, canvas_height);
+                v.draw_frame(&mut d, &placement);
+            }
+
+            if show_subtitles {
+                if let Some(text) = v.subtitle_text() {
+                    let style = &config.subtitles;
+                    match &self.subtitle_font {
+                        Some(font) => {
+                            draw_subtitles(&mut d, font, style, &text, canvas_width, canvas_height)
+                        }
+                        None => draw_subtitles(
+                            &mut d,
+                            &self.default_font,
+                            style,
+                            &text,
+                            canvas_width,
+                            canvas_height,
+                        ),
+                    }
+                }
+            }
+            viewport.end();
+
+            if show_stats {
+                d.draw_fps(0, 0);
+                if let Some(stats) = v.frame_stats() {
+                    d.draw_text(&stats, 12, 24, 18, Color::WHITE);
+                }
+            }
+        } else if !addresses.is_empty() {
+            d.draw_fps(0, 0);
+
+            d.draw_text(format!("SimplePresenter Projector\n\nScreen: {screen_width}X{screen_height}").as_str(), 12, 12, 18, Color::WHITE);
+
+            let mut x = screen_width / 2.0 - 240.0;
+            let mut y = screen_height / 2.0 - 240.0;
+            d.draw_text("Server ready:", x as i32, y as i32, 18, Color::WHITE);
+            x += 20.0;
+            y += 24.0;
+            for address in addresses {
+                d.draw_text(address, x as i32, y as i32, 24, Color::WHITE);
+                y += 24.0;
+            }
+        }
+
+        self.edge_blend.draw(&mut d);
+        if show_test_grid {
+            draw_test_grid(&mut d, screen_width, screen_height);
+        }
+        self.warp.end(&config.warp);
+        if capture.is_some() {
+            self.captured = Some(self.capture.read());
+        }
+    }
+
+    fn snapshot(&mut self) -> Option<Frame> {
+        self.captured.take()
+    }
+}
@@ -1,3 +1,4 @@
+use std::fmt;
 use std::sync::Arc;
 use crate::ProjectorCommand;
 use futures_util::{SinkExt, StreamExt};
@@ -7,57 +8,102 @@ use tokio_tungstenite::connect_async;
 use tokio_tungstenite::tungstenite::client::IntoClientRequest;
 use tokio_tungstenite::tungstenite::Utf8Bytes;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionStatus {
+    Connecting,
+    Connected,
+    Disconnected,
+}
+
+impl fmt::Display for ConnectionStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConnectionStatus::Connecting => write!(f, "Connecting"),
+            ConnectionStatus::Connected => write!(f, "Connected"),
+            ConnectionStatus::Disconnected => write!(f, "Disconnected"),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct ProjectorClient {
     cmd_tx: UnboundedSender<ProjectorCommand>,
-    evt_rx: Arc<Mutex<UnboundedReceiver<ProjectorCommand>>>,
+    subscribers: Arc<Mutex<Vec<UnboundedSender<ProjectorCommand>>>>,
+    status: Arc<Mutex<ConnectionStatus>>,
 }
 
 impl ProjectorClient {
     pub fn new(ws_url: &str) -> Self {
         let (cmd_tx, cmd_rx) = unbounded_channel::<ProjectorCommand>();
-        let (evt_tx, evt_rx) = unbounded_channel::<ProjectorCommand>();
-        Self::start_ws_task(ws_url.to_string(), cmd_rx, evt_tx);
-        ProjectorClient { cmd_tx, evt_rx: Arc::new(Mutex::new(evt_rx)) }
+        let subscribers = Arc::new(Mutex::new(Vec::new()));
+        let status = Arc::new(Mutex::new(ConnectionStatus::Connecting));
+        Self::start_ws_task(
+            ws_url.to_string(),
+            cmd_rx,
+            subscribers.clone(),
+            status.clone(),
+        );
+        ProjectorClient {
+            cmd_tx,
+            subscribers,
+            status,
+        }
     }
 
     fn start_ws_task(
         ws_url: String,
         mut cmd_rx: UnboundedReceiver<ProjectorCommand>,
-        evt_tx: UnboundedSender<ProjectorCommand>,
+        subscribers: Arc<Mutex<Vec<UnboundedSender<ProjectorCommand>>>>,
+        status: Arc<Mutex<ConnectionStatus>>,
     ) {
+        let set_status = move |new_status| {
+            if let Ok(mut status) = status.lock() {
+                *status = new_status;
+            }
+        };
+
         tokio::spawn(async move {
-            let mut request = ws_url.into_client_request().unwrap();
-            let (ws_stream, _) = connect_async(request).await.expect("Failed to connect");
+            let connection = match ws_url.as_str().into_client_request() {
+                Ok(request) => connect_async(request).await,
+                Err(e) => Err(e),
+            };
+            let ws_stream = match connection {
+                Ok((ws_stream, _)) => ws_stream,
+                Err(e) => {
+                    eprintln!("Failed to connect to {}: {}", ws_url, e);
+                    set_status(ConnectionStatus::Disconnected);
+                    return;
+                }
+            };
             println!("Connected to projector process");
+            set_status(ConnectionStatus::Connected);
 
             let (mut write, mut read) = ws_stream.split();
 
-            tokio::spawn({
-                let evt_tx = evt_tx.clone();
-                async move {
-                    while let Some(msg) = read.next().await {
-                        match msg {
-                            Ok(tokio_tungstenite::tungstenite::Message::Text(txt)) => {
-                                match txt.as_ref() {
-                                    "VideoEnded" => {
-                                        let _ = evt_tx.send(ProjectorCommand::VideoEnded);
-                                    }
-                                    _ => println!("Projector says: {}", txt),
-                                }
+            tokio::spawn(async move {
+                while let Some(msg) = read.next().await {
+                    match msg {
+                        Ok(tokio_tungstenite::tungstenite::Message::Text(txt)) => {
+                            match ProjectorCommand::from_message(txt.as_ref()) {
+                                Some(evt) => publish(&subscribers, evt),
+                                None => println!("Projector says: {}", txt),
+                            }
+                        }
+                        Ok(tokio_tungstenite::tungstenite::Message::Binary(data)) => {
+                            match ProjectorCommand::from_binary(&data) {
+                                Some(evt) => publish(&subscribers, evt),
+                                None => println!("Unknown binary message of {} bytes", data.len()),
                             }
-                            Ok(_) => {}
-                            Err(e) => eprintln!("WS error: {}", e),
                         }
+                        Ok(_) => {}
+                        Err(e) => eprintln!("WS error: {}", e),
                     }
                 }
+                set_status(ConnectionStatus::Disconnected);
             });
 
             while let Some(cmd) = cmd_rx.recv().await {
-                let payload = match cmd {
-                    ProjectorCommand::Start { path, skip } => format!("START\n{}\n{}", path, skip),
-                    ProjectorCommand::VideoEnded => "VideoEnded".to_string(),
-                };
+                let payload = cmd.to_message();
                 if let Err(e) = write
                     .send(tokio_tungstenite::tungstenite::Message::Text(
                         Utf8Bytes::from(payload),
@@ -75,12 +121,25 @@ impl ProjectorClient {
         let _ = self.cmd_tx.send(cmd);
     }
 
+    pub fn status(&self) -> ConnectionStatus {
+        self.status
+            .lock()
+            .map(|status| *status)
+            .unwrap_or(ConnectionStatus::Disconnected)
+    }
+
+    /// Returns a receiver for every event the projector sends from now on
     pub fn subscribe(&self) -> UnboundedReceiver<ProjectorCommand> {
         let (tx, rx) = unbounded_channel();
-        let mut inner = self.evt_rx.lock().unwrap();
-        while let Ok(evt) = inner.try_recv() {
-            let _ = tx.send(evt);
-        }
+        self.subscribers.lock().unwrap().push(tx);
         rx
     }
 }
+
+fn publish(subscribers: &Mutex<Vec<UnboundedSender<ProjectorCommand>>>, evt: ProjectorCommand) {
+    // drop subscribers whose receiver is gone
+    subscribers
+        .lock()
+        .unwrap()
+        .retain(|tx| tx.send(evt.clone()).is_ok());
+}
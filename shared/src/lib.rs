@@ -1,7 +1,11 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::fmt;
 
 pub mod client;
 pub mod path;
+pub mod warp;
+
+pub use warp::Warp;
 
 #[derive(Debug, Clone, Serialize)]
 pub enum Skip {
@@ -10,15 +14,17 @@ pub enum Skip {
     Time(u32),
 }
 
-impl Skip {
-    pub fn to_string(&self) -> String {
+impl fmt::Display for Skip {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Skip::VideoEnd => "VideoEnd".to_string(),
-            Skip::None => "None".to_string(),
-            Skip::Time(secs) => format!("Time({})", secs),
+            Skip::VideoEnd => write!(f, "VideoEnd"),
+            Skip::None => write!(f, "None"),
+            Skip::Time(secs) => write!(f, "Time({})", secs),
         }
     }
+}
 
+impl Skip {
     pub fn from_string(s: &str) -> Option<Self> {
         match s {
             "VideoEnd" => Some(Skip::VideoEnd),
@@ -38,9 +44,804 @@ pub struct Source {
     skip: Skip,
 }
 
+/// An audio or subtitle track found by the projector's probe
+#[derive(Debug, Clone, Serialize)]
+pub struct Track {
+    pub index: u32,
+    pub language: Option<String>,
+    pub codec: String,
+    /// Sidecar file the track is read from, `None` for tracks embedded in the media
+    pub sidecar: Option<String>,
+    /// GStreamer stream id of an embedded track. Only the projector selects streams, so
+    /// it isn't sent to the GUI
+    #[serde(skip)]
+    pub stream_id: Option<String>,
+}
+
+impl fmt::Display for Track {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{};{};{};{}",
+            self.index,
+            self.language.as_deref().unwrap_or(""),
+            self.codec,
+            self.sidecar.as_deref().unwrap_or("")
+        )
+    }
+}
+
+impl Track {
+    pub fn from_string(s: &str) -> Option<Self> {
+        // the sidecar path goes last so it may contain the separator
+        let mut parts = s.splitn(4, ';');
+        let index = parts.next()?.parse::<u32>().ok()?;
+        let language = parts.next()?;
+        let codec = parts.next()?;
+        let sidecar = parts.next()?;
+
+        Some(Track {
+            index,
+            language: (!language.is_empty()).then(|| language.to_string()),
+            codec: codec.to_string(),
+            sidecar: (!sidecar.is_empty()).then(|| sidecar.to_string()),
+            stream_id: None,
+        })
+    }
+
+    /// Human readable name, starting with the index so it can be parsed back
+    pub fn label(&self) -> String {
+        let language = self.language.as_deref().unwrap_or("und");
+        match &self.sidecar {
+            Some(sidecar) => format!("{}: {} ({})", self.index, language, sidecar),
+            None => format!("{}: {} ({})", self.index, language, self.codec),
+        }
+    }
+
+    /// Reverses [`Track::label`], returning the track index
+    pub fn index_from_label(label: &str) -> Option<u32> {
+        label.split(':').next()?.trim().parse::<u32>().ok()
+    }
+}
+
+/// A display the projector can open its output on
+#[derive(Debug, Clone, Serialize)]
+pub struct Monitor {
+    pub index: u32,
+    pub name: String,
+    pub width: i32,
+    pub height: i32,
+    /// Position on the desktop
+    pub x: i32,
+    pub y: i32,
+}
+
+impl fmt::Display for Monitor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{};{};{};{};{};{}",
+            self.index, self.width, self.height, self.x, self.y, self.name
+        )
+    }
+}
+
+impl Monitor {
+    pub fn from_string(s: &str) -> Option<Self> {
+        // the name goes last so it may contain the separator
+        let mut parts = s.splitn(6, ';');
+        Some(Monitor {
+            index: parts.next()?.parse().ok()?,
+            width: parts.next()?.parse().ok()?,
+            height: parts.next()?.parse().ok()?,
+            x: parts.next()?.parse().ok()?,
+            y: parts.next()?.parse().ok()?,
+            name: parts.next()?.to_string(),
+        })
+    }
+}
+
+/// Per-channel audio levels in dBFS, as measured by the projector
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AudioLevels {
+    pub peak: Vec<f64>,
+    pub rms: Vec<f64>,
+}
+
+impl AudioLevels {
+    fn to_lines(&self) -> Vec<String> {
+        vec![
+            format!("peak={}", join_values(&self.peak)),
+            format!("rms={}", join_values(&self.rms)),
+        ]
+    }
+
+    fn from_lines<'a>(lines: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut levels = AudioLevels::default();
+        for line in lines {
+            if let Some(values) = line.strip_prefix("peak=") {
+                levels.peak = split_values(values);
+            } else if let Some(values) = line.strip_prefix("rms=") {
+                levels.rms = split_values(values);
+            }
+        }
+        levels
+    }
+}
+
+fn join_values(values: &[f64]) -> String {
+    values
+        .iter()
+        .map(|v| format!("{:.1}", v))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn split_values(s: &str) -> Vec<f64> {
+    s.split(',').filter_map(|v| v.parse().ok()).collect()
+}
+
+/// How the video is placed on the projector output. Configs spell it like `Display`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum ScaleMode {
+    /// The whole frame is visible, letterboxed if needed
+    Fit,
+    /// The output is covered, cropping the frame
+    Fill,
+    /// The output is covered, ignoring the aspect ratio
+    Stretch,
+    /// One source pixel per output pixel
+    Native,
+    /// `zoom` is relative to `Fit`, the pan is a fraction of the output size
+    Custom { zoom: f32, pan_x: f32, pan_y: f32 },
+}
+
+impl fmt::Display for ScaleMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScaleMode::Fit => write!(f, "fit"),
+            ScaleMode::Fill => write!(f, "fill"),
+            ScaleMode::Stretch => write!(f, "stretch"),
+            ScaleMode::Native => write!(f, "native"),
+            ScaleMode::Custom { zoom, pan_x, pan_y } => {
+                write!(f, "custom:{},{},{}", zoom, pan_x, pan_y)
+            }
+        }
+    }
+}
+
+impl ScaleMode {
+    pub fn from_string(s: &str) -> Option<Self> {
+        match s {
+            "fit" => Some(ScaleMode::Fit),
+            "fill" => Some(ScaleMode::Fill),
+            "stretch" => Some(ScaleMode::Stretch),
+            "native" => Some(ScaleMode::Native),
+            _ => {
+                let mut values = s.strip_prefix("custom:")?.split(',');
+                let mut next = || values.next()?.trim().parse::<f32>().ok();
+                Some(ScaleMode::Custom {
+                    zoom: next()?,
+                    pan_x: next()?,
+                    pan_y: next()?,
+                })
+            }
+        }
+    }
+}
+
+impl TryFrom<String> for ScaleMode {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        ScaleMode::from_string(&s).ok_or_else(|| {
+            format!(
+                "unknown scale mode '{}', expected fit, fill, stretch, native or \
+                 custom:zoom,pan_x,pan_y",
+                s
+            )
+        })
+    }
+}
+
+impl From<ScaleMode> for String {
+    fn from(mode: ScaleMode) -> String {
+        mode.to_string()
+    }
+}
+
+/// What the projector shows where the video doesn't cover the output, e.g. the bars
+/// around vertical footage. Configs spell it like `Display`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Background {
+    Black,
+    /// A blurred, darkened copy of the video
+    Blur,
+    /// An image file, as seen from the projector
+    Image(String),
+}
+
+impl fmt::Display for Background {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Background::Black => write!(f, "black"),
+            Background::Blur => write!(f, "blur"),
+            Background::Image(path) => write!(f, "image:{}", path),
+        }
+    }
+}
+
+impl Background {
+    pub fn from_string(s: &str) -> Option<Self> {
+        match s {
+            "black" => Some(Background::Black),
+            "blur" => Some(Background::Blur),
+            _ => s
+                .strip_prefix("image:")
+                .filter(|path| !path.is_empty())
+                .map(|path| Background::Image(path.to_string())),
+        }
+    }
+}
+
+impl TryFrom<String> for Background {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        Background::from_string(&s)
+            .ok_or_else(|| format!("unknown background '{}', expected black, blur or image:path", s))
+    }
+}
+
+impl From<Background> for String {
+    fn from(background: Background) -> String {
+        background.to_string()
+    }
+}
+
+/// Encoding of output snapshots
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub enum ImageFormat {
+    #[default]
+    Png,
+    Jpeg,
+}
 
-#[derive(Debug)]
+impl fmt::Display for ImageFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImageFormat::Png => write!(f, "png"),
+            ImageFormat::Jpeg => write!(f, "jpeg"),
+        }
+    }
+}
+
+impl ImageFormat {
+    pub fn from_string(s: &str) -> Option<Self> {
+        match s {
+            "png" => Some(ImageFormat::Png),
+            "jpeg" | "jpg" => Some(ImageFormat::Jpeg),
+            _ => None,
+        }
+    }
+
+    pub fn media_type(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "image/png",
+            ImageFormat::Jpeg => "image/jpeg",
+        }
+    }
+}
+
+/// Per-item playback settings sent along with `Start`
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ItemOptions {
+    /// Index into the probed audio tracks, `None` plays the default one
+    pub audio_track: Option<u32>,
+    /// Index into the probed subtitle tracks, `None` disables subtitles
+    pub subtitle_track: Option<u32>,
+    /// Base gain of the item, `None` plays at unity gain
+    pub volume: Option<f64>,
+    pub fade_in_ms: u32,
+    pub fade_out_ms: u32,
+    /// Audio output for this item, overriding the projector's default
+    pub audio_device: Option<String>,
+    /// Forces deinterlacing on or off, `None` follows the stream's interlacing flags
+    pub deinterlace: Option<bool>,
+    /// `None` uses the projector's default
+    pub scale_mode: Option<ScaleMode>,
+    /// Clockwise degrees in 90° steps, `None` uses the projector's default
+    pub rotation: Option<u32>,
+    /// `None` uses the projector's default
+    pub background: Option<Background>,
+    /// Load the item paused and wait for `StartAt`
+    pub cue: bool,
+}
+
+impl ItemOptions {
+    pub fn to_lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        if let Some(track) = self.audio_track {
+            lines.push(format!("audio_track={}", track));
+        }
+        if let Some(track) = self.subtitle_track {
+            lines.push(format!("subtitle_track={}", track));
+        }
+        if let Some(volume) = self.volume {
+            lines.push(format!("volume={}", volume));
+        }
+        if self.fade_in_ms > 0 {
+            lines.push(format!("fade_in_ms={}", self.fade_in_ms));
+        }
+        if self.fade_out_ms > 0 {
+            lines.push(format!("fade_out_ms={}", self.fade_out_ms));
+        }
+        if let Some(device) = &self.audio_device {
+            lines.push(format!("audio_device={}", device));
+        }
+        if let Some(deinterlace) = self.deinterlace {
+            lines.push(format!("deinterlace={}", deinterlace));
+        }
+        if let Some(mode) = self.scale_mode {
+            lines.push(format!("scale_mode={}", mode));
+        }
+        if let Some(rotation) = self.rotation {
+            lines.push(format!("rotation={}", rotation));
+        }
+        if let Some(background) = &self.background {
+            lines.push(format!("background={}", background));
+        }
+        if self.cue {
+            lines.push("cue=true".to_string());
+        }
+        lines
+    }
+
+    /// Unknown keys are ignored so older projectors keep working with newer GUIs
+    pub fn from_lines<'a>(lines: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut options = ItemOptions::default();
+        for line in lines {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            match key {
+                "audio_track" => options.audio_track = value.parse().ok(),
+                "subtitle_track" => options.subtitle_track = value.parse().ok(),
+                "volume" => options.volume = value.parse().ok(),
+                "fade_in_ms" => options.fade_in_ms = value.parse().unwrap_or(0),
+                "fade_out_ms" => options.fade_out_ms = value.parse().unwrap_or(0),
+                "audio_device" => options.audio_device = Some(value.to_string()),
+                "deinterlace" => options.deinterlace = value.parse().ok(),
+                "scale_mode" => options.scale_mode = ScaleMode::from_string(value),
+                "rotation" => options.rotation = value.parse().ok(),
+                "background" => options.background = Background::from_string(value),
+                "cue" => options.cue = value.parse().unwrap_or(false),
+                _ => {}
+            }
+        }
+        options
+    }
+}
+
+#[derive(Debug, Clone)]
 pub enum ProjectorCommand {
-    Start { path: String, skip: String },
-    VideoEnded
-}
\ No newline at end of file
+    Start {
+        path: String,
+        skip: String,
+        options: ItemOptions,
+    },
+    VideoEnded,
+    Probe {
+        path: String,
+    },
+    ProbeResult {
+        path: String,
+        audio_tracks: Vec<Track>,
+        subtitle_tracks: Vec<Track>,
+        /// Integrated loudness of the first audio track in LUFS, if it has been measured
+        /// already
+        loudness: Option<f64>,
+    },
+    /// Sent once a loudness analysis of one of the file's audio tracks finishes
+    Loudness {
+        path: String,
+        audio_track: u32,
+        lufs: f64,
+    },
+    SelectAudioTrack(u32),
+    SelectSubtitleTrack(Option<u32>),
+    ShowSubtitles(bool),
+    SetMasterVolume(f64),
+    SetMute(bool),
+    /// Gain currently applied by the projector, master volume and fades included
+    VolumeLevel(f64),
+    ListAudioDevices,
+    AudioDevices(Vec<String>),
+    AudioLevels(AudioLevels),
+    /// Set while an item that has audio stays silent
+    SilenceWarning(bool),
+    /// Changes the placement of the current item
+    SetScaleMode(ScaleMode),
+    SetRotation(u32),
+    /// Replaces the output warp, the projector saves it to its config
+    SetWarp(Warp),
+    GetWarp,
+    /// The projector's current warp, in reply to `GetWarp`
+    Warp(Warp),
+    /// Draws a calibration grid over the output while adjusting the warp
+    ShowTestGrid(bool),
+    /// Starts the cued item when the shared clock reaches `base_time`, in nanoseconds
+    StartAt {
+        base_time: u64,
+    },
+    GetClockTime,
+    /// The shared clock's time in nanoseconds, in reply to `GetClockTime`
+    ClockTime(u64),
+    ListMonitors,
+    Monitors(Vec<Monitor>),
+    /// Moves the output window to another monitor, the projector saves it to its config
+    MoveOutput {
+        monitor: u32,
+        fullscreen: bool,
+        /// 0 for the projector's own window, 1 and up for the windows of its `outputs`
+        output: u32,
+    },
+    /// Asks for the frame on the output, scaled down to `max_width` unless it is 0
+    Snapshot {
+        max_width: u32,
+        format: ImageFormat,
+    },
+    /// The encoded output frame, in reply to `Snapshot`. Sent as a binary message, see
+    /// [`ProjectorCommand::to_binary`]
+    SnapshotImage {
+        format: ImageFormat,
+        width: u32,
+        height: u32,
+        data: Vec<u8>,
+    },
+    /// Streams the output as JPEG frames at up to `fps`, scaled down to `max_width`
+    /// unless it is 0. Frames are dropped rather than queued while one is being encoded
+    StartPreview {
+        max_width: u32,
+        fps: u32,
+    },
+    StopPreview,
+    /// A frame of the preview stream, sent as a binary message like `SnapshotImage`
+    PreviewFrame {
+        width: u32,
+        height: u32,
+        /// When the projector drew the frame, in milliseconds since the Unix epoch
+        captured_at: u64,
+        data: Vec<u8>,
+    },
+}
+
+impl ProjectorCommand {
+    /// Encodes the command as a websocket text message, one field per line
+    pub fn to_message(&self) -> String {
+        match self {
+            ProjectorCommand::Start {
+                path,
+                skip,
+                options,
+            } => {
+                let mut lines = vec!["START".to_string(), path.clone(), skip.clone()];
+                lines.extend(options.to_lines());
+                lines.join("\n")
+            }
+            ProjectorCommand::VideoEnded => "VideoEnded".to_string(),
+            ProjectorCommand::Probe { path } => format!("PROBE\n{}", path),
+            ProjectorCommand::ProbeResult {
+                path,
+                audio_tracks,
+                subtitle_tracks,
+                loudness,
+            } => {
+                let mut lines = vec!["PROBE_RESULT".to_string(), path.clone()];
+                lines.extend(audio_tracks.iter().map(|t| format!("audio={}", t)));
+                lines.extend(subtitle_tracks.iter().map(|t| format!("subtitle={}", t)));
+                lines.extend(loudness.map(|l| format!("loudness={}", l)));
+                lines.join("\n")
+            }
+            ProjectorCommand::Loudness {
+                path,
+                audio_track,
+                lufs,
+            } => format!("LOUDNESS\n{}\n{}\n{}", path, lufs, audio_track),
+            ProjectorCommand::SelectAudioTrack(track) => format!("SELECT_AUDIO\n{}", track),
+            ProjectorCommand::SelectSubtitleTrack(track) => match track {
+                Some(track) => format!("SELECT_SUBTITLE\n{}", track),
+                None => "SELECT_SUBTITLE\nNone".to_string(),
+            },
+            ProjectorCommand::ShowSubtitles(visible) => format!("SHOW_SUBTITLES\n{}", visible),
+            ProjectorCommand::SetMasterVolume(volume) => format!("MASTER_VOLUME\n{}", volume),
+            ProjectorCommand::SetMute(muted) => format!("MUTE\n{}", muted),
+            ProjectorCommand::VolumeLevel(level) => format!("VOLUME_LEVEL\n{}", level),
+            ProjectorCommand::ListAudioDevices => "LIST_AUDIO_DEVICES".to_string(),
+            ProjectorCommand::AudioDevices(devices) => {
+                let mut lines = vec!["AUDIO_DEVICES".to_string()];
+                lines.extend(devices.iter().cloned());
+                lines.join("\n")
+            }
+            ProjectorCommand::AudioLevels(levels) => {
+                let mut lines = vec!["AUDIO_LEVELS".to_string()];
+                lines.extend(levels.to_lines());
+                lines.join("\n")
+            }
+            ProjectorCommand::SilenceWarning(silent) => format!("SILENCE_WARNING\n{}", silent),
+            ProjectorCommand::SetScaleMode(mode) => format!("SCALE_MODE\n{}", mode),
+            ProjectorCommand::SetRotation(rotation) => format!("ROTATION\n{}", rotation),
+            ProjectorCommand::SetWarp(warp) => {
+                let mut lines = vec!["WARP".to_string()];
+                lines.extend(warp.to_lines());
+                lines.join("\n")
+            }
+            ProjectorCommand::GetWarp => "GET_WARP".to_string(),
+            ProjectorCommand::Warp(warp) => {
+                let mut lines = vec!["CURRENT_WARP".to_string()];
+                lines.extend(warp.to_lines());
+                lines.join("\n")
+            }
+            ProjectorCommand::ShowTestGrid(visible) => format!("TEST_GRID\n{}", visible),
+            ProjectorCommand::StartAt { base_time } => format!("START_AT\n{}", base_time),
+            ProjectorCommand::GetClockTime => "GET_CLOCK".to_string(),
+            ProjectorCommand::ClockTime(time) => format!("CLOCK\n{}", time),
+            ProjectorCommand::ListMonitors => "LIST_MONITORS".to_string(),
+            ProjectorCommand::Monitors(monitors) => {
+                let mut lines = vec!["MONITORS".to_string()];
+                lines.extend(monitors.iter().map(|m| m.to_string()));
+                lines.join("\n")
+            }
+            ProjectorCommand::MoveOutput {
+                monitor,
+                fullscreen,
+                output,
+            } => format!("MOVE_OUTPUT\n{}\n{}\n{}", monitor, fullscreen, output),
+            ProjectorCommand::Snapshot { max_width, format } => {
+                format!("SNAPSHOT\n{}\n{}", max_width, format)
+            }
+            // the data follows in the binary message
+            ProjectorCommand::SnapshotImage {
+                format,
+                width,
+                height,
+                ..
+            } => format!("SNAPSHOT_IMAGE\n{}\n{}\n{}", format, width, height),
+            ProjectorCommand::StartPreview { max_width, fps } => {
+                format!("START_PREVIEW\n{}\n{}", max_width, fps)
+            }
+            ProjectorCommand::StopPreview => "STOP_PREVIEW".to_string(),
+            ProjectorCommand::PreviewFrame {
+                width,
+                height,
+                captured_at,
+                ..
+            } => format!("PREVIEW_FRAME\n{}\n{}\n{}", width, height, captured_at),
+        }
+    }
+
+    /// Commands carrying image data go out as binary messages: the text message, an
+    /// empty line and the data. `None` for commands sent as text
+    pub fn to_binary(&self) -> Option<Vec<u8>> {
+        let data = match self {
+            ProjectorCommand::SnapshotImage { data, .. } => data,
+            ProjectorCommand::PreviewFrame { data, .. } => data,
+            _ => return None,
+        };
+        let mut message = self.to_message().into_bytes();
+        message.extend_from_slice(b"\n\n");
+        message.extend_from_slice(data);
+        Some(message)
+    }
+
+    pub fn from_binary(message: &[u8]) -> Option<Self> {
+        let split = message.windows(2).position(|w| w == b"\n\n")?;
+        let text = std::str::from_utf8(&message[..split]).ok()?;
+        let data = message[split + 2..].to_vec();
+        match text.split('\n').collect::<Vec<_>>()[..] {
+            ["SNAPSHOT_IMAGE", format, width, height] => Some(ProjectorCommand::SnapshotImage {
+                format: ImageFormat::from_string(format)?,
+                width: width.parse().ok()?,
+                height: height.parse().ok()?,
+                data,
+            }),
+            ["PREVIEW_FRAME", width, height, captured_at] => Some(ProjectorCommand::PreviewFrame {
+                width: width.parse().ok()?,
+                height: height.parse().ok()?,
+                captured_at: captured_at.parse().ok()?,
+                data,
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn from_message(text: &str) -> Option<Self> {
+        let parts: Vec<_> = text.split('\n').collect();
+        match parts[0] {
+            "START" if parts.len() >= 3 => Some(ProjectorCommand::Start {
+                path: parts[1].to_string(),
+                skip: parts[2].to_string(),
+                options: ItemOptions::from_lines(parts[3..].iter().copied()),
+            }),
+            "VideoEnded" => Some(ProjectorCommand::VideoEnded),
+            "PROBE" if parts.len() >= 2 => Some(ProjectorCommand::Probe {
+                path: parts[1].to_string(),
+            }),
+            "PROBE_RESULT" if parts.len() >= 2 => {
+                let mut audio_tracks = Vec::new();
+                let mut subtitle_tracks = Vec::new();
+                let mut loudness = None;
+                for line in &parts[2..] {
+                    if let Some(track) = line.strip_prefix("audio=") {
+                        audio_tracks.extend(Track::from_string(track));
+                    } else if let Some(track) = line.strip_prefix("subtitle=") {
+                        subtitle_tracks.extend(Track::from_string(track));
+                    } else if let Some(lufs) = line.strip_prefix("loudness=") {
+                        loudness = lufs.parse().ok();
+                    }
+                }
+                Some(ProjectorCommand::ProbeResult {
+                    path: parts[1].to_string(),
+                    audio_tracks,
+                    subtitle_tracks,
+                    loudness,
+                })
+            }
+            "LOUDNESS" if parts.len() >= 3 => {
+                parts[2].parse().ok().map(|lufs| ProjectorCommand::Loudness {
+                    path: parts[1].to_string(),
+                    audio_track: parts.get(3).and_then(|t| t.parse().ok()).unwrap_or(0),
+                    lufs,
+                })
+            }
+            "SELECT_AUDIO" if parts.len() >= 2 => {
+                parts[1].parse().ok().map(ProjectorCommand::SelectAudioTrack)
+            }
+            "SELECT_SUBTITLE" if parts.len() >= 2 => Some(ProjectorCommand::SelectSubtitleTrack(
+                parts[1].parse().ok(),
+            )),
+            "SHOW_SUBTITLES" if parts.len() >= 2 => {
+                parts[1].parse().ok().map(ProjectorCommand::ShowSubtitles)
+            }
+            "MASTER_VOLUME" if parts.len() >= 2 => {
+                parts[1].parse().ok().map(ProjectorCommand::SetMasterVolume)
+            }
+            "MUTE" if parts.len() >= 2 => parts[1].parse().ok().map(ProjectorCommand::SetMute),
+            "VOLUME_LEVEL" if parts.len() >= 2 => {
+                parts[1].parse().ok().map(ProjectorCommand::VolumeLevel)
+            }
+            "LIST_AUDIO_DEVICES" => Some(ProjectorCommand::ListAudioDevices),
+            "AUDIO_DEVICES" => Some(ProjectorCommand::AudioDevices(
+                parts[1..].iter().map(|d| d.to_string()).collect(),
+            )),
+            "AUDIO_LEVELS" => Some(ProjectorCommand::AudioLevels(AudioLevels::from_lines(
+                parts[1..].iter().copied(),
+            ))),
+            "SILENCE_WARNING" if parts.len() >= 2 => {
+                parts[1].parse().ok().map(ProjectorCommand::SilenceWarning)
+            }
+            "SCALE_MODE" if parts.len() >= 2 => {
+                ScaleMode::from_string(parts[1]).map(ProjectorCommand::SetScaleMode)
+            }
+            "ROTATION" if parts.len() >= 2 => parts[1].parse().ok().map(ProjectorCommand::SetRotation),
+            "WARP" => Some(ProjectorCommand::SetWarp(Warp::from_lines(
+                parts[1..].iter().copied(),
+            ))),
+            "GET_WARP" => Some(ProjectorCommand::GetWarp),
+            "CURRENT_WARP" => Some(ProjectorCommand::Warp(Warp::from_lines(
+                parts[1..].iter().copied(),
+            ))),
+            "TEST_GRID" if parts.len() >= 2 => {
+                parts[1].parse().ok().map(ProjectorCommand::ShowTestGrid)
+            }
+            "START_AT" if parts.len() >= 2 => parts[1]
+                .parse()
+                .ok()
+                .map(|base_time| ProjectorCommand::StartAt { base_time }),
+            "GET_CLOCK" => Some(ProjectorCommand::GetClockTime),
+            "CLOCK" if parts.len() >= 2 => parts[1].parse().ok().map(ProjectorCommand::ClockTime),
+            "LIST_MONITORS" => Some(ProjectorCommand::ListMonitors),
+            "MONITORS" => Some(ProjectorCommand::Monitors(
+                parts[1..].iter().filter_map(|m| Monitor::from_string(m)).collect(),
+            )),
+            "SNAPSHOT" => Some(ProjectorCommand::Snapshot {
+                max_width: parts.get(1).and_then(|w| w.parse().ok()).unwrap_or(0),
+                format: parts
+                    .get(2)
+                    .and_then(|f| ImageFormat::from_string(f))
+                    .unwrap_or_default(),
+            }),
+            "START_PREVIEW" => Some(ProjectorCommand::StartPreview {
+                max_width: parts.get(1).and_then(|w| w.parse().ok()).unwrap_or(0),
+                fps: parts.get(2).and_then(|f| f.parse().ok()).unwrap_or(10),
+            }),
+            "STOP_PREVIEW" => Some(ProjectorCommand::StopPreview),
+            "MOVE_OUTPUT" if parts.len() >= 2 => {
+                parts[1].parse().ok().map(|monitor| ProjectorCommand::MoveOutput {
+                    monitor,
+                    fullscreen: parts.get(2).and_then(|f| f.parse().ok()).unwrap_or(true),
+                    output: parts.get(3).and_then(|o| o.parse().ok()).unwrap_or(0),
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn item_options_round_trip() {
+        let options = ItemOptions {
+            audio_track: Some(1),
+            subtitle_track: Some(2),
+            volume: Some(0.5),
+            fade_in_ms: 1500,
+            fade_out_ms: 250,
+            audio_device: Some("hw:1=front".to_string()),
+            deinterlace: Some(false),
+            scale_mode: Some(ScaleMode::Custom {
+                zoom: 1.25,
+                pan_x: -0.1,
+                pan_y: 0.05,
+            }),
+            rotation: Some(270),
+            background: Some(Background::Image("/srv/bg=1.png".to_string())),
+            cue: true,
+        };
+
+        let lines = options.to_lines();
+        let parsed = ItemOptions::from_lines(lines.iter().map(|l| l.as_str()));
+        assert_eq!(parsed.to_lines(), lines);
+        assert_eq!(parsed.scale_mode, options.scale_mode);
+        assert_eq!(parsed.background, options.background);
+    }
+
+    #[test]
+    fn default_item_options_have_no_lines() {
+        assert!(ItemOptions::default().to_lines().is_empty());
+    }
+
+    #[test]
+    fn item_options_ignore_unknown_keys() {
+        let options = ItemOptions::from_lines(["volume=2", "future_option=1", "no separator"]);
+        assert_eq!(options.volume, Some(2.0));
+        assert_eq!(options.to_lines(), vec!["volume=2"]);
+    }
+
+    #[test]
+    fn scale_modes_parse() {
+        assert_eq!(ScaleMode::from_string("fit"), Some(ScaleMode::Fit));
+        assert_eq!(ScaleMode::from_string("native"), Some(ScaleMode::Native));
+        assert_eq!(
+            ScaleMode::from_string("custom:2, 0.25, -0.5"),
+            Some(ScaleMode::Custom {
+                zoom: 2.0,
+                pan_x: 0.25,
+                pan_y: -0.5
+            })
+        );
+        assert_eq!(ScaleMode::from_string("Fit"), None);
+        assert_eq!(ScaleMode::from_string("custom:2,0.25"), None);
+        assert!(ScaleMode::try_from("zoom".to_string()).is_err());
+    }
+
+    #[test]
+    fn scale_modes_display_as_parsed() {
+        for mode in [
+            ScaleMode::Fill,
+            ScaleMode::Stretch,
+            ScaleMode::Custom {
+                zoom: 0.8,
+                pan_x: 0.0,
+                pan_y: 0.1,
+            },
+        ] {
+            assert_eq!(ScaleMode::from_string(&mode.to_string()), Some(mode));
+        }
+    }
+}
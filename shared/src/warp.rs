@@ -0,0 +1,298 @@
+use serde::{Deserialize, Serialize};
+
+/// Geometry correction applied to the whole output, for projectors that aren't square
+/// to the screen
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Warp {
+    /// Where the top-left, top-right, bottom-right and bottom-left corners of the image
+    /// land, as fractions of the output size
+    pub corners: [(f32, f32); 4],
+    /// Mesh size in cells, 0 disables the mesh
+    pub columns: u32,
+    pub rows: u32,
+    /// Offsets of the `(columns + 1) * (rows + 1)` mesh points row by row, as fractions
+    /// of the corner-pinned image
+    pub mesh: Vec<(f32, f32)>,
+}
+
+impl Default for Warp {
+    fn default() -> Self {
+        Warp {
+            corners: [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)],
+            columns: 0,
+            rows: 0,
+            mesh: Vec::new(),
+        }
+    }
+}
+
+impl Warp {
+    pub fn is_identity(&self) -> bool {
+        self.corners == Warp::default().corners && self.mesh.iter().all(|p| *p == (0.0, 0.0))
+    }
+
+    /// Whether a mesh is set. A config may give only one of the sizes, which counts as none
+    pub fn has_mesh(&self) -> bool {
+        self.columns > 0 && self.rows > 0
+    }
+
+    /// Changes the mesh size, which resets the mesh points
+    pub fn set_mesh_size(&mut self, columns: u32, rows: u32) {
+        let (columns, rows) = if columns == 0 || rows == 0 {
+            (0, 0)
+        } else {
+            (columns, rows)
+        };
+        self.columns = columns;
+        self.rows = rows;
+        self.mesh = if columns == 0 {
+            Vec::new()
+        } else {
+            vec![(0.0, 0.0); ((columns + 1) * (rows + 1)) as usize]
+        };
+    }
+
+    /// Offset of a mesh point, zero without a mesh
+    pub fn mesh_point(&self, column: u32, row: u32) -> (f32, f32) {
+        self.mesh
+            .get((row * (self.columns + 1) + column) as usize)
+            .copied()
+            .unwrap_or((0.0, 0.0))
+    }
+
+    /// Where the image point `u, v` lands on the output, both as fractions of the size
+    pub fn map(&self, u: f32, v: f32) -> (f32, f32) {
+        let (x, y) = self.mesh_offset(u, v);
+        Homography::from_corners(&self.corners).map(u + x, v + y)
+    }
+
+    /// Reverses the corner pin alone, e.g. to turn a dragged mesh point back into an offset
+    pub fn unpin(&self, x: f32, y: f32) -> (f32, f32) {
+        Homography::from_corners(&self.corners).inverse().map(x, y)
+    }
+
+    /// Offset at `u, v` interpolated from the surrounding mesh points
+    fn mesh_offset(&self, u: f32, v: f32) -> (f32, f32) {
+        if !self.has_mesh() {
+            return (0.0, 0.0);
+        }
+
+        let x = u.clamp(0.0, 1.0) * self.columns as f32;
+        let y = v.clamp(0.0, 1.0) * self.rows as f32;
+        let column = (x as u32).min(self.columns - 1);
+        let row = (y as u32).min(self.rows - 1);
+        let (fx, fy) = (x - column as f32, y - row as f32);
+
+        let top = lerp(self.mesh_point(column, row), self.mesh_point(column + 1, row), fx);
+        let bottom = lerp(
+            self.mesh_point(column, row + 1),
+            self.mesh_point(column + 1, row + 1),
+            fx,
+        );
+        lerp(top, bottom, fy)
+    }
+
+    pub(crate) fn to_lines(&self) -> Vec<String> {
+        let mut lines = vec![format!("corners={}", join_points(&self.corners))];
+        if self.has_mesh() {
+            lines.push(format!("mesh={},{}", self.columns, self.rows));
+            lines.push(format!("points={}", join_points(&self.mesh)));
+        }
+        lines
+    }
+
+    pub(crate) fn from_lines<'a>(lines: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut warp = Warp::default();
+        let mut mesh = Vec::new();
+        for line in lines {
+            if let Some(points) = line.strip_prefix("corners=") {
+                if let Ok(corners) = split_points(points).try_into() {
+                    warp.corners = corners;
+                }
+            } else if let Some(size) = line.strip_prefix("mesh=") {
+                if let Some((columns, rows)) = size.split_once(',') {
+                    warp.set_mesh_size(columns.parse().unwrap_or(0), rows.parse().unwrap_or(0));
+                }
+            } else if let Some(points) = line.strip_prefix("points=") {
+                mesh = split_points(points);
+            }
+        }
+        // a mesh that doesn't match its size is dropped rather than misplaced
+        if mesh.len() == warp.mesh.len() {
+            warp.mesh = mesh;
+        }
+        warp
+    }
+}
+
+fn join_points(points: &[(f32, f32)]) -> String {
+    points
+        .iter()
+        .map(|(x, y)| format!("{},{}", x, y))
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+fn split_points(s: &str) -> Vec<(f32, f32)> {
+    s.split(';')
+        .filter_map(|point| {
+            let (x, y) = point.split_once(',')?;
+            Some((x.trim().parse().ok()?, y.trim().parse().ok()?))
+        })
+        .collect()
+}
+
+fn lerp(a: (f32, f32), b: (f32, f32), t: f32) -> (f32, f32) {
+    (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t)
+}
+
+/// Projective map of the unit square onto four corners, as a row-major 3x3 matrix
+struct Homography([f32; 9]);
+
+impl Homography {
+    fn from_corners(corners: &[(f32, f32); 4]) -> Self {
+        let [(x0, y0), (x1, y1), (x2, y2), (x3, y3)] = *corners;
+        let (dx1, dy1) = (x1 - x2, y1 - y2);
+        let (dx2, dy2) = (x3 - x2, y3 - y2);
+        let (dx3, dy3) = (x0 - x1 + x2 - x3, y0 - y1 + y2 - y3);
+
+        let denominator = dx1 * dy2 - dx2 * dy1;
+        // a parallelogram, or corners dragged into a line, only needs the affine part
+        let (g, h) = if (dx3 == 0.0 && dy3 == 0.0) || denominator.abs() < f32::EPSILON {
+            (0.0, 0.0)
+        } else {
+            (
+                (dx3 * dy2 - dx2 * dy3) / denominator,
+                (dx1 * dy3 - dx3 * dy1) / denominator,
+            )
+        };
+
+        Homography([
+            x1 - x0 + g * x1,
+            x3 - x0 + h * x3,
+            x0,
+            y1 - y0 + g * y1,
+            y3 - y0 + h * y3,
+            y0,
+            g,
+            h,
+            1.0,
+        ])
+    }
+
+    fn inverse(&self) -> Self {
+        let [a, b, c, d, e, f, g, h, i] = self.0;
+        // the adjugate, the determinant cancels out in `map`
+        Homography([
+            e * i - f * h,
+            c * h - b * i,
+            b * f - c * e,
+            f * g - d * i,
+            a * i - c * g,
+            c * d - a * f,
+            d * h - e * g,
+            b * g - a * h,
+            a * e - b * d,
+        ])
+    }
+
+    fn map(&self, u: f32, v: f32) -> (f32, f32) {
+        let [a, b, c, d, e, f, g, h, i] = self.0;
+        let w = g * u + h * v + i;
+        ((a * u + b * v + c) / w, (d * u + e * v + f) / w)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEYSTONE: [(f32, f32); 4] = [(0.1, 0.05), (0.95, 0.0), (0.85, 1.0), (0.0, 0.9)];
+
+    fn assert_near(actual: (f32, f32), expected: (f32, f32)) {
+        assert!(
+            (actual.0 - expected.0).abs() < 1e-4 && (actual.1 - expected.1).abs() < 1e-4,
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn homography_maps_the_unit_square_onto_the_corners() {
+        let homography = Homography::from_corners(&KEYSTONE);
+        let square = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+        for (corner, (u, v)) in KEYSTONE.iter().zip(square) {
+            assert_near(homography.map(u, v), *corner);
+        }
+    }
+
+    #[test]
+    fn homography_inverse_round_trips() {
+        let homography = Homography::from_corners(&KEYSTONE);
+        let inverse = homography.inverse();
+        for point in [(0.5, 0.5), (0.25, 0.75), (0.9, 0.1)] {
+            let (x, y) = homography.map(point.0, point.1);
+            assert_near(inverse.map(x, y), point);
+        }
+    }
+
+    #[test]
+    fn parallelogram_is_affine() {
+        let corners = [(0.1, 0.0), (1.0, 0.0), (0.9, 1.0), (0.0, 1.0)];
+        assert_near(Homography::from_corners(&corners).map(0.5, 0.5), (0.5, 0.5));
+    }
+
+    #[test]
+    fn default_warp_is_identity() {
+        let warp = Warp::default();
+        assert!(warp.is_identity());
+        assert_near(warp.map(0.3, 0.7), (0.3, 0.7));
+    }
+
+    #[test]
+    fn mesh_offsets_are_interpolated() {
+        let mut warp = Warp::default();
+        warp.set_mesh_size(2, 1);
+        // lift the middle column of points
+        warp.mesh[1] = (0.0, -0.1);
+        warp.mesh[4] = (0.0, -0.1);
+
+        assert_near(warp.map(0.5, 0.5), (0.5, 0.4));
+        assert_near(warp.map(0.25, 0.5), (0.25, 0.45));
+        assert_near(warp.map(0.0, 0.5), (0.0, 0.5));
+    }
+
+    #[test]
+    fn single_mesh_size_counts_as_no_mesh() {
+        let mut warp = Warp::default();
+        warp.set_mesh_size(3, 0);
+        assert!(!warp.has_mesh());
+        assert!(warp.mesh.is_empty());
+
+        // a config may set one of the sizes directly
+        warp.columns = 3;
+        assert!(!warp.has_mesh());
+        assert_near(warp.map(0.5, 0.5), (0.5, 0.5));
+    }
+
+    #[test]
+    fn lines_round_trip() {
+        let mut warp = Warp {
+            corners: KEYSTONE,
+            ..Warp::default()
+        };
+        warp.set_mesh_size(1, 1);
+        warp.mesh[3] = (0.05, -0.02);
+
+        let lines = warp.to_lines();
+        assert_eq!(Warp::from_lines(lines.iter().map(|l| l.as_str())), warp);
+    }
+
+    #[test]
+    fn mismatched_mesh_is_dropped() {
+        let warp = Warp::from_lines(["mesh=1,1", "points=0.1,0.1;0,0"]);
+        assert_eq!(warp.mesh, vec![(0.0, 0.0); 4]);
+    }
+}
@@ -0,0 +1,145 @@
+import asyncio
+import os
+import subprocess
+import sys
+import tempfile
+import zlib
+
+import websockets
+
+URL = "ws://127.0.0.1:8795"
+CONFIG = """
+listen = "127.0.0.1:8795"
+
+[audio]
+device = "null"
+
+[headless]
+width = 640
+height = 360
+dump = "{dump}"
+dump_every = 10
+"""
+
+
+async def connect():
+    # the projector needs a moment to start its server
+    for _ in range(50):
+        try:
+            return await websockets.connect(URL)
+        except OSError:
+            await asyncio.sleep(0.2)
+    raise RuntimeError(f"Projector did not start listening on {URL}")
+
+
+def read_png(path):
+    with open(path, "rb") as f:
+        return decode_png(f.read())
+
+
+def decode_png(data):
+    """
+    Decodes an 8 bit RGB or RGBA PNG as written by the projector into rows of bytes and
+    the number of bytes per pixel, without needing an imaging library.
+    """
+    position = 8
+    idat = b""
+    while position < len(data):
+        length = int.from_bytes(data[position:position + 4], "big")
+        kind = data[position + 4:position + 8]
+        body = data[position + 8:position + 8 + length]
+        if kind == b"IHDR":
+            width = int.from_bytes(body[0:4], "big")
+            height = int.from_bytes(body[4:8], "big")
+            bpp = {2: 3, 6: 4}[body[9]]
+        elif kind == b"IDAT":
+            idat += body
+        position += length + 12
+
+    raw = zlib.decompress(idat)
+    stride = width * bpp
+    rows = []
+    previous = bytearray(stride)
+    for y in range(height):
+        start = y * (stride + 1)
+        kind = raw[start]
+        row = bytearray(raw[start + 1:start + 1 + stride])
+        for x in range(stride):
+            left = row[x - bpp] if x >= bpp else 0
+            up = previous[x]
+            up_left = previous[x - bpp] if x >= bpp else 0
+            if kind == 1:
+                row[x] = (row[x] + left) & 0xFF
+            elif kind == 2:
+                row[x] = (row[x] + up) & 0xFF
+            elif kind == 3:
+                row[x] = (row[x] + (left + up) // 2) & 0xFF
+            elif kind == 4:
+                estimate = left + up - up_left
+                pa, pb, pc = abs(estimate - left), abs(estimate - up), abs(estimate - up_left)
+                predictor = left if pa <= pb and pa <= pc else up if pb <= pc else up_left
+                row[x] = (row[x] + predictor) & 0xFF
+        rows.append(row)
+        previous = row
+    return rows, bpp
+
+
+def has_picture(path):
+    # scaled video edges may leave a few dark but not quite black pixels
+    rows, bpp = read_png(path)
+    return any(
+        max(row[x:x + 3]) > 16 for row in rows for x in range(0, len(row), bpp)
+    )
+
+
+async def play(path, timeout, dump):
+    connection = await connect()
+    # whatever was dumped so far shows the idle screen
+    before_start = set(os.listdir(dump)) if os.path.isdir(dump) else set()
+    await connection.send(f"START\n{path}\nVideoEnd")
+    while True:
+        reply = await asyncio.wait_for(connection.recv(), timeout)
+        if reply == "VideoEnded":
+            break
+    await connection.close()
+    return before_start
+
+
+def main(projector, path, timeout):
+    """
+    Plays a file on a headless projector until it ends and checks that frames showing
+    the video were rendered, for machines without a display.
+    """
+    with tempfile.TemporaryDirectory() as workdir:
+        dump = os.path.join(workdir, "frames")
+        config = os.path.join(workdir, "projector.toml")
+        with open(config, "w") as f:
+            f.write(CONFIG.format(dump=dump))
+
+        process = subprocess.Popen([projector, config, "--headless"], cwd=workdir)
+        try:
+            before_start = asyncio.run(play(path, timeout, dump))
+        finally:
+            process.terminate()
+            process.wait()
+
+        frames = sorted(os.listdir(dump)) if os.path.isdir(dump) else []
+        if not frames:
+            print("No frames were rendered")
+            sys.exit(1)
+        print(f"Rendered {len(frames)} frames")
+
+        played = [frame for frame in frames if frame not in before_start]
+        if not any(has_picture(os.path.join(dump, frame)) for frame in played):
+            print(f"None of the {len(played)} frames dumped after START show the video")
+            sys.exit(1)
+
+
+if __name__ == "__main__":
+    # python headless_playback.py target/debug/projector video.mp4
+    if len(sys.argv) < 3:
+        print("Usage: python headless_playback.py PROJECTOR_EXECUTABLE VIDEO_FILE [TIMEOUT]")
+        sys.exit(1)
+
+    timeout = float(sys.argv[3]) if len(sys.argv) > 3 else 60
+    main(os.path.abspath(sys.argv[1]), os.path.abspath(sys.argv[2]), timeout)
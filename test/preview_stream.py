@@ -0,0 +1,49 @@
+import asyncio
+import sys
+import time
+
+import websockets
+
+
+async def main(url, seconds, max_width, fps):
+    """
+    Watches the projector's preview stream for a while and reports the frame rate and
+    latency it arrives at. The latency is only right when both machines keep the same
+    time, which they do on loopback.
+    """
+    async with websockets.connect(url) as connection:
+        await connection.send(f"START_PREVIEW\n{max_width}\n{fps}")
+
+        latencies = []
+        end = time.monotonic() + seconds
+        while (remaining := end - time.monotonic()) > 0:
+            try:
+                reply = await asyncio.wait_for(connection.recv(), remaining)
+            except asyncio.TimeoutError:
+                break
+            if not isinstance(reply, bytes) or not reply.startswith(b"PREVIEW_FRAME\n"):
+                continue
+            header = reply.split(b"\n\n", 1)[0].decode().split("\n")
+            latencies.append(time.time() * 1000 - int(header[3]))
+
+        await connection.send("STOP_PREVIEW")
+
+    if not latencies:
+        print("No preview frames arrived")
+        sys.exit(1)
+    print(
+        f"{len(latencies) / seconds:.1f} fps, latency {min(latencies):.0f} ms min, "
+        f"{sum(latencies) / len(latencies):.0f} ms average, {max(latencies):.0f} ms max"
+    )
+
+
+if __name__ == "__main__":
+    # python preview_stream.py ws://127.0.0.1:8765 10
+    if len(sys.argv) < 2:
+        print("Usage: python preview_stream.py PROJECTOR_URL [SECONDS] [MAX_WIDTH] [FPS]")
+        sys.exit(1)
+
+    seconds = float(sys.argv[2]) if len(sys.argv) > 2 else 10
+    max_width = int(sys.argv[3]) if len(sys.argv) > 3 else 480
+    fps = int(sys.argv[4]) if len(sys.argv) > 4 else 10
+    asyncio.run(main(sys.argv[1], seconds, max_width, fps))
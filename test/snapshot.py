@@ -0,0 +1,36 @@
+import asyncio
+import sys
+
+import websockets
+
+
+async def main(url, path, max_width):
+    """
+    Saves what the projector shows right now, as PNG or JPEG depending on the file
+    extension.
+    """
+    image_format = "jpeg" if path.lower().endswith((".jpg", ".jpeg")) else "png"
+    async with websockets.connect(url) as connection:
+        await connection.send(f"SNAPSHOT\n{max_width}\n{image_format}")
+
+        # events arrive as text, the image as the only binary message
+        while True:
+            reply = await connection.recv()
+            if isinstance(reply, bytes):
+                break
+
+    header, data = reply.split(b"\n\n", 1)
+    _, _, width, height = header.decode().split("\n")
+    with open(path, "wb") as f:
+        f.write(data)
+    print(f"Saved a {width}x{height} snapshot to {path}")
+
+
+if __name__ == "__main__":
+    # python snapshot.py ws://127.0.0.1:8765 output.png 1280
+    if len(sys.argv) < 3:
+        print("Usage: python snapshot.py PROJECTOR_URL FILE [MAX_WIDTH]")
+        sys.exit(1)
+
+    max_width = int(sys.argv[3]) if len(sys.argv) > 3 else 0
+    asyncio.run(main(sys.argv[1], sys.argv[2], max_width))
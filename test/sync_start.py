@@ -0,0 +1,92 @@
+import asyncio
+import statistics
+import sys
+
+import websockets
+
+from headless_playback import decode_png
+
+# Time the projectors get to preroll the cued item before they start together
+START_DELAY_NS = 1_000_000_000
+# Snapshots compared once playing, small enough to decode quickly
+CHECKS = 5
+SNAPSHOT_WIDTH = 160
+# Mean difference per colour channel, out of 255, up to which two snapshots show the
+# same frame. Frames one apart in moving footage usually differ by more
+MAX_DIFFERENCE = 6
+
+
+async def snapshot(connection):
+    await connection.send(f"SNAPSHOT\n{SNAPSHOT_WIDTH}\npng")
+    # events arrive as text, the image as the only binary message
+    while True:
+        reply = await connection.recv()
+        if isinstance(reply, bytes):
+            return reply.split(b"\n\n", 1)[1]
+
+
+def difference(first, second):
+    (rows_a, bpp_a), (rows_b, bpp_b) = decode_png(first), decode_png(second)
+    if len(rows_a) != len(rows_b) or len(rows_a[0]) != len(rows_b[0]) or bpp_a != bpp_b:
+        raise RuntimeError("The projectors' outputs differ in size")
+    total, count = 0, 0
+    for row_a, row_b in zip(rows_a, rows_b):
+        for x in range(0, len(row_a), bpp_a):
+            for channel in range(3):
+                total += abs(row_a[x + channel] - row_b[x + channel])
+                count += 1
+    return total / max(count, 1)
+
+
+async def main(path, urls):
+    """
+    Cues the same file on every projector, asks the first one for the shared clock's
+    time and starts them all on the same base time. Then snapshots all of them at once
+    a few times and checks they show the same frame, which needs outputs of the same
+    size.
+    """
+    connections = [await websockets.connect(url) for url in urls]
+
+    for connection in connections:
+        await connection.send(f"START\n{path}\nVideoEnd\ncue=true")
+
+    # projectors send events as well, wait for the clock reply
+    await connections[0].send("GET_CLOCK")
+    while True:
+        reply = await connections[0].recv()
+        if reply.startswith("CLOCK\n"):
+            now = int(reply.split("\n")[1])
+            break
+
+    base_time = now + START_DELAY_NS
+    for connection in connections:
+        await connection.send(f"START_AT\n{base_time}")
+    print(f"Started {len(urls)} projectors at {base_time}")
+
+    await asyncio.sleep(START_DELAY_NS / 1e9 + 1)
+    differences = []
+    for _ in range(CHECKS):
+        images = await asyncio.gather(*(snapshot(c) for c in connections))
+        worst = max(difference(images[0], image) for image in images[1:])
+        print(f"Snapshots differ by {worst:.1f}")
+        differences.append(worst)
+        await asyncio.sleep(0.5)
+
+    for connection in connections:
+        await connection.close()
+
+    if statistics.median(differences) > MAX_DIFFERENCE:
+        print("The projectors are not showing the same frames")
+        sys.exit(1)
+    print("The projectors play in sync")
+
+
+if __name__ == "__main__":
+    # Two projectors on one machine, e.g. with `listen = "127.0.0.1:8765"` and
+    # `listen = "127.0.0.1:8775"` in their configs:
+    # python sync_start.py video.mp4 ws://127.0.0.1:8765 ws://127.0.0.1:8775
+    if len(sys.argv) < 4:
+        print("Usage: python sync_start.py VIDEO_FILE PROJECTOR_URL PROJECTOR_URL...")
+        sys.exit(1)
+
+    asyncio.run(main(sys.argv[1], sys.argv[2:]))